@@ -3,14 +3,16 @@ pub mod double_path;
 pub mod euler;
 mod path;
 mod scored;
+pub mod verify;
 
 use self::bhandari::BandhariGraph;
+use self::euler::WalkedEdge;
 
 use crate::io::GraphRepresentation;
 
 use petgraph::{
     algo::{bellman_ford::Paths, FloatMeasure, Measure},
-    stable_graph::{IndexType, NodeIndex, StableDiGraph},
+    stable_graph::{EdgeIndex, IndexType, NodeIndex, StableDiGraph},
     visit::{Data, GraphBase, IntoEdges},
 };
 
@@ -27,6 +29,9 @@ pub type DistanceMap<G, E> = HashMap<<G as GraphBase>::NodeId, E>;
 //algorithm, we need to "manually" construct an undirected graph using the
 //directed graph type. That is, we will need to add two edges for each
 //edge in our adjacency list (one in each direction).
+//The arcs of input edge `i` are added at indices `2i` (in the input direction) and
+//`2i + 1`, which is how `input_edge` and `input_edge_arc` map between the two.
+#[allow(clippy::type_complexity)]
 pub fn make_graph<G, Ix>(
     gr: GraphRepresentation<G::NodeWeight, G::EdgeWeight, Ix>,
 ) -> (
@@ -62,6 +67,20 @@ where
     (g, node_index_mapper)
 }
 
+/// The input edge an arc of a graph built by `make_graph` was made from, and the direction
+/// the arc runs in
+pub fn input_edge<Ix: IndexType>(arc: EdgeIndex<Ix>) -> WalkedEdge<usize> {
+    WalkedEdge {
+        id: arc.index() / 2,
+        forward: arc.index().is_multiple_of(2),
+    }
+}
+
+/// The arc of a graph built by `make_graph` running along input edge `edge`
+pub fn input_edge_arc<Ix: IndexType>(edge: WalkedEdge<usize>) -> EdgeIndex<Ix> {
+    EdgeIndex::new(2 * edge.id + usize::from(!edge.forward))
+}
+
 pub fn trim_graph_at_max_distance<N, E, Ix>(
     g: &mut StableDiGraph<N, E, Ix>,
     distance_map: &HashMap<NodeIndex<Ix>, E>,
@@ -101,6 +120,7 @@ where
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn path_results_to_distance_and_predecessors<E, Ix>(
     paths: Paths<NodeIndex<Ix>, E>,
 ) -> (
@@ -129,7 +149,7 @@ where
         .zip(paths.distances.iter())
         .map(|(i, cost)| (NodeIndex::<Ix>::from(i.into()), cost))
         .for_each(|(node, cost)| {
-            if predecessor_map.get(&node).is_some() {
+            if predecessor_map.contains_key(&node) {
                 distance_map.insert(node, *cost);
             }
         });
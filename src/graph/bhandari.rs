@@ -1,15 +1,16 @@
 use crate::graph::{
-    euler::EulerGraph, path::Path, path_results_to_distance_and_predecessors, DistanceMap,
-    PredecessorMap,
+    euler::EulerGraph, input_edge, input_edge_arc, path::Path,
+    path_results_to_distance_and_predecessors, DistanceMap, PredecessorMap,
 };
 use num::Bounded;
 use petgraph::{
     algo::{bellman_ford, FloatMeasure, Measure},
     data::DataMap,
     stable_graph::{IndexType, NodeIndex, StableDiGraph},
-    visit::{Data, GraphBase, NodeIndexable, Visitable},
+    visit::{Data, EdgeRef, GraphBase, NodeIndexable, Visitable},
 };
 use std::{
+    cmp::Ordering,
     fmt::Debug,
     hash::Hash,
     ops::{Mul, Neg, RemAssign},
@@ -83,26 +84,25 @@ where
         //Continue here translating from Bhandari.cpp line 26
         let u = &p.sequence[i];
         let v = &p.sequence[i + 1];
-        let mut w: <G as Data>::EdgeWeight;
-
-        //Remove edges
-        if let Some(edge_id) = g.find_edge(*u, *v) {
-            //println!("Removing edge {:?}", &edge_id);
-            if let Some(weight) = g.remove_edge(edge_id) {
-                //print!(" with weight {:?}", &weight);
-                w = weight;
-                let _temp = g.add_edge(*u, *v, w + rg.inf_2);
-                //println!("Added edge {:?}", temp);
-            }
-        }
 
-        if let Some(edge_id) = g.find_edge(*v, *u) {
-            //println!("Removing edge {:?}", &edge_id);
-            if let Some(weight) = g.remove_edge(edge_id) {
-                //print!(" with weight {:?}", &weight);
-                w = weight;
-                let _temp = g.add_edge(*v, *u, -w);
-                //println!("Added edge {:?}", temp);
+        //The path runs along the shortest of any parallel arcs from u to v, and the arc
+        //negated must be the opposing arc of that same edge, or a parallel edge would close
+        //a negative cycle
+        if let Some(arc) = g
+            .edges_connecting(*u, *v)
+            .min_by(|e1, e2| {
+                e1.weight()
+                    .partial_cmp(e2.weight())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .map(|e| e.id())
+        {
+            let w = g[arc];
+            g[arc] = w + rg.inf_2;
+
+            let twin = input_edge_arc(input_edge(arc).reversed());
+            if let Some(weight) = g.edge_weight_mut(twin) {
+                *weight = -w;
             }
         }
 
@@ -124,11 +124,9 @@ where
             //println!("{:?}", &reverse_path);
             Some(reverse_path)
         } else {
-            println!("Failed to get reverse path");
             None
         }
     } else {
-        println!("Failed to execute bellman_ford");
         None
     }
     // let (mod_distance_map, mod_predecessor_map) =
@@ -136,32 +134,40 @@ where
 }
 
 //In the future, think about implementing this as an associated method on EulerGraph
-pub fn unweave_paths<G, E, Ix>(p1: Path<G, E>, p2: Path<G, E>) -> EulerGraph<G>
+/// Combines two paths from the same source to the same target into the edges of a circuit,
+/// or `None` when the second path walks an edge of the first again in the same direction
+pub fn unweave_paths<G, E, Ix>(
+    graph: &StableDiGraph<G::NodeWeight, E, Ix>,
+    p1: Path<G, E>,
+    p2: Path<G, E>,
+) -> Option<EulerGraph<G>>
 where
     G: Visitable + NodeIndexable + Data<EdgeWeight = E> + GraphBase<NodeId = NodeIndex<Ix>>,
     G::NodeId: Copy,
     G::NodeId: Eq + Hash,
     E: Copy + Measure + Bounded,
+    Ix: IndexType,
 {
     let mut circuit_set = EulerGraph::<G>::new();
 
     for i in 0..(p1.sequence.len() - 1) {
-        circuit_set
-            .edges
-            .insert((p1.sequence[i], p1.sequence[i + 1]));
+        circuit_set.edges.push((p1.sequence[i], p1.sequence[i + 1]));
     }
 
+    //Walking back along an edge of the first path cancels it out; walking the same way as
+    //the first path is only a circuit when a second, parallel edge is taken, as otherwise
+    //the circuit would walk the one edge twice
     for i in 0..(p2.sequence.len() - 1) {
-        match circuit_set.edges.get(&(p2.sequence[i + 1], p2.sequence[i])) {
-            None => {
-                circuit_set
-                    .edges
-                    .insert((p2.sequence[i], p2.sequence[i + 1]));
+        let (u, v) = (p2.sequence[i], p2.sequence[i + 1]);
+        match circuit_set.edges.iter().position(|pair| *pair == (v, u)) {
+            Some(pos) => {
+                circuit_set.edges.swap_remove(pos);
             }
-            Some(_) => {
-                circuit_set
-                    .edges
-                    .remove(&(p2.sequence[i + 1], p2.sequence[i]));
+            None => {
+                if circuit_set.edges.contains(&(u, v)) && graph.edges_connecting(u, v).count() < 2 {
+                    return None;
+                }
+                circuit_set.edges.push((u, v));
             }
         }
     }
@@ -171,5 +177,59 @@ where
         circuit_set.vertices.insert(*v);
     });
 
-    circuit_set
+    Some(circuit_set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Graph = StableDiGraph<u32, f64, u32>;
+
+    fn path(sequence: &[NodeIndex<u32>]) -> Path<Graph, f64> {
+        let mut p = Path::new();
+        p.sequence = sequence.to_vec();
+        p
+    }
+
+    //Nodes 0 to 3 joined in a square, with `repeats` edges from 0 to 1
+    fn square(repeats: usize) -> (Graph, Vec<NodeIndex<u32>>) {
+        let mut graph = Graph::default();
+        let nodes: Vec<_> = (0..4).map(|label| graph.add_node(label)).collect();
+        let mut edges = vec![(0, 1); repeats];
+        edges.extend([(1, 2), (2, 3), (3, 0)]);
+        for (u, v) in edges {
+            graph.add_edge(nodes[u], nodes[v], 1.0);
+            graph.add_edge(nodes[v], nodes[u], 1.0);
+        }
+        (graph, nodes)
+    }
+
+    #[test]
+    fn walking_back_cancels_edges() {
+        let (graph, n) = square(1);
+        let h = unweave_paths(
+            &graph,
+            path(&[n[0], n[1], n[2]]),
+            path(&[n[0], n[3], n[2], n[1], n[2]]),
+        )
+        .unwrap();
+
+        let mut edges = h.edges.clone();
+        edges.sort();
+        assert_eq!(
+            edges,
+            vec![(n[0], n[1]), (n[0], n[3]), (n[1], n[2]), (n[3], n[2])]
+        );
+    }
+
+    #[test]
+    fn repeating_an_edge_needs_a_parallel_one() {
+        let (graph, n) = square(1);
+        assert!(unweave_paths(&graph, path(&[n[0], n[1]]), path(&[n[0], n[1]])).is_none());
+
+        let (graph, n) = square(2);
+        let h = unweave_paths(&graph, path(&[n[0], n[1]]), path(&[n[0], n[1]])).unwrap();
+        assert_eq!(h.edges, vec![(n[0], n[1]), (n[0], n[1])]);
+    }
 }
@@ -15,12 +15,14 @@ use petgraph::{
     stable_graph::{IndexType, NodeIndex, StableDiGraph},
     visit::{GraphBase, NodeIndexable, Visitable},
 };
+use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashSet};
 use std::{
     fmt::Debug,
     ops::{Add, AddAssign, Mul, Neg, RemAssign},
 };
 
+#[allow(clippy::type_complexity)]
 pub fn double_path<G, Ix>(
     source: NodeIndex<Ix>,
     rg: &BandhariGraph<StableDiGraph<G::NodeWeight, G::EdgeWeight, Ix>, G::EdgeWeight, Ix>,
@@ -66,7 +68,7 @@ where
 
         while let Some(MaxScored(_node_score, node)) = max_dist_heap.pop() {
             //println!("Popped node {:?}", &node);
-            if failed_nodes.get(node).is_some() {
+            if failed_nodes.contains(node) {
                 continue;
             }
             if let Some(p1) = get_path_from_predecessors::<G, G::EdgeWeight>(
@@ -76,14 +78,25 @@ where
                 &distance_map,
             ) {
                 //println!("Path One: {:?}", &p1);
-                if let Some(p2) = get_edge_disjoint_path(&rg, &p1) {
-                    let mut h = unweave_paths(p1, p2);
+                if let Some(p2) = get_edge_disjoint_path(rg, &p1) {
+                    //Paths that walk an edge twice make no circuit, so the target is given up
+                    let mut h = match unweave_paths(&rg.graph, p1, p2) {
+                        Some(h) => h,
+                        None => {
+                            failed_nodes.insert(*node);
+                            continue;
+                        }
+                    };
 
+                    //Paths run along the shortest of any parallel edges
                     h.edges.iter().for_each(|(u, v)| {
-                        if let Some(e) = rg.graph.find_edge(*u, *v) {
-                            h.length += *rg.graph.edge_weight(e).unwrap();
-                        } else if let Some(e) = rg.graph.find_edge(*v, *u) {
-                            h.length += *rg.graph.edge_weight(e).unwrap();
+                        if let Some(w) = rg
+                            .graph
+                            .edges_connecting(*u, *v)
+                            .map(|e| *e.weight())
+                            .min_by(|w1, w2| w1.partial_cmp(w2).unwrap_or(Ordering::Equal))
+                        {
+                            h.length += w;
                         }
                     });
                     _iterations += 1;
@@ -116,7 +129,6 @@ where
         }
         Some((h_lower, h_upper))
     } else {
-        println!("Failed to execute first instance of bellman_ford");
         None
     }
 }
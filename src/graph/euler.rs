@@ -1,12 +1,15 @@
+use anyhow::{anyhow, Error};
 use petgraph::{
     data::DataMap,
-    stable_graph::{EdgeIndex, IndexType, NodeIndex, StableDiGraph},
-    visit::{Data, NodeIndexable},
+    stable_graph::{EdgeIndex, EdgeReference, IndexType, NodeIndex, StableDiGraph},
+    visit::{Data, EdgeRef, NodeIndexable},
 };
+use serde::{Deserialize, Serialize};
 
-use crate::graph::{GraphBase, Measure};
+use crate::graph::{input_edge, GraphBase, Measure};
 
 use std::{
+    cmp::Ordering,
     collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     hash::Hash,
@@ -19,10 +22,22 @@ where
     G: GraphBase + Data,
 {
     pub length: G::EdgeWeight,
-    pub edges: HashSet<(G::NodeId, G::NodeId)>,
+    /// A pair appears once for each edge joining its nodes that the circuit walks, so it
+    /// may appear more than once when the nodes are joined by parallel edges
+    pub edges: Vec<(G::NodeId, G::NodeId)>,
     pub vertices: HashSet<G::NodeId>,
 }
 
+impl<G> Default for EulerGraph<G>
+where
+    G: GraphBase + Data,
+    G::EdgeWeight: Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<G> EulerGraph<G>
 where
     G: GraphBase + Data,
@@ -31,12 +46,39 @@ where
     pub fn new() -> Self {
         Self {
             length: G::EdgeWeight::default(),
-            edges: HashSet::new(),
+            edges: Vec::new(),
             vertices: HashSet::new(),
         }
     }
 }
 
+/// An input edge as walked by a circuit, identified by `id` (by default its position in the
+/// input edge list)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WalkedEdge<K> {
+    pub id: K,
+    /// Whether the edge is walked from its input start node to its input end node
+    pub forward: bool,
+}
+
+impl<K> WalkedEdge<K> {
+    /// The same edge, walked the other way
+    pub fn reversed(self) -> Self {
+        Self {
+            id: self.id,
+            forward: !self.forward,
+        }
+    }
+
+    /// The same walk, with the edge identified by `f(id)` instead
+    pub fn map_id<T>(self, f: impl FnOnce(K) -> T) -> WalkedEdge<T> {
+        WalkedEdge {
+            id: f(self.id),
+            forward: self.forward,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct EulerCircuit<G>
 where
@@ -44,10 +86,21 @@ where
 {
     pub length: G::EdgeWeight,
     pub node_pair_list: Vec<(G::NodeId, G::NodeId)>,
-    pub edge_list: Vec<G::EdgeId>,
+    /// The input edge walked by each step of the circuit
+    pub edge_list: Vec<WalkedEdge<usize>>,
     pub ordered_node_weight_list: Vec<G::NodeWeight>,
 }
 
+impl<G> Default for EulerCircuit<G>
+where
+    G: GraphBase + Data + DataMap,
+    G::EdgeWeight: Default + Sum,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<G> EulerCircuit<G>
 where
     G: GraphBase + Data + DataMap,
@@ -67,7 +120,7 @@ pub fn make_euler_circuit<G, Ix>(
     ref_graph: &StableDiGraph<G::NodeWeight, G::EdgeWeight, Ix>,
     egraph: &EulerGraph<G>,
     source: G::NodeId,
-) -> EulerCircuit<G>
+) -> Result<EulerCircuit<G>, Error>
 where
     G: GraphBase<NodeId = NodeIndex<Ix>, EdgeId = EdgeIndex<Ix>> + NodeIndexable + Data + DataMap,
     G::NodeId: Hash + Eq,
@@ -101,7 +154,7 @@ where
         };
     });
 
-    let node_order = hierholzer_new::<G, G::EdgeWeight>(&vertex_edge_mapper, source);
+    let node_order = hierholzer_new::<G>(&vertex_edge_mapper, source);
 
     let ordered_node_weight_list: Vec<<G as Data>::NodeWeight> = node_order
         .iter()
@@ -114,33 +167,58 @@ where
         .map(|(s, t)| (*s, *t))
         .collect();
 
-    let edge_list: Vec<G::EdgeId> = node_pair_list
+    //Nodes may be joined by several edges, so each step walks the shortest one it has not
+    //already walked, rather than whichever arc find_edge happens to return; only once all
+    //of them have been walked is one walked again
+    let mut used = HashSet::<usize>::new();
+    let mut weights = Vec::<G::EdgeWeight>::new();
+    let shortest = |e1: &EdgeReference<G::EdgeWeight, Ix>,
+                    e2: &EdgeReference<G::EdgeWeight, Ix>| {
+        e1.weight()
+            .partial_cmp(e2.weight())
+            .unwrap_or(Ordering::Equal)
+    };
+    let edge_list = node_pair_list
         .iter()
-        .filter_map(|(s, t)| ref_graph.find_edge(*s, *t))
-        .collect();
+        .enumerate()
+        .map(|(position, (s, t))| {
+            let arc = ref_graph
+                .edges_connecting(*s, *t)
+                .filter(|e| !used.contains(&input_edge(e.id()).id))
+                .min_by(shortest)
+                .or_else(|| ref_graph.edges_connecting(*s, *t).min_by(shortest))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "circuit step {} from node {} to node {} is not an edge of the graph",
+                        position,
+                        s.index(),
+                        t.index()
+                    )
+                })?;
+            let walked = input_edge(arc.id());
+            used.insert(walked.id);
+            weights.push(*arc.weight());
+            Ok(walked)
+        })
+        .collect::<Result<Vec<WalkedEdge<usize>>, Error>>()?;
 
-    let length = edge_list
-        .iter()
-        .filter_map(|e| ref_graph.edge_weight(*e))
-        .copied()
-        .sum();
+    let length = weights.into_iter().sum();
 
-    EulerCircuit {
+    Ok(EulerCircuit {
         length,
         node_pair_list,
         edge_list,
         ordered_node_weight_list,
-    }
+    })
 }
 
-fn hierholzer_new<G, E>(
+fn hierholzer_new<G>(
     vertex_edge_mapper: &HashMap<G::NodeId, VecDeque<G::NodeId>>,
     source: G::NodeId,
 ) -> VecDeque<G::NodeId>
 where
     G: GraphBase,
     G::NodeId: Hash + Eq + Debug,
-    E: Copy + Measure + Default,
 {
     let mut v_e_mapper = vertex_edge_mapper.clone();
     let mut curr_path: VecDeque<G::NodeId> = VecDeque::new();
@@ -149,9 +227,11 @@ where
     curr_path.push_back(source);
     let mut current_vertex = source;
 
+    //A vertex without edges (only ever the source, when there are none at all) is left at
+    //once, as one whose edges have all been walked
     while !curr_path.is_empty() {
-        if let Some(adj_list) = v_e_mapper.get_mut(&current_vertex) {
-            if !adj_list.is_empty() {
+        match v_e_mapper.get_mut(&current_vertex) {
+            Some(adj_list) if !adj_list.is_empty() => {
                 curr_path.push_back(current_vertex);
                 let next_vertex = adj_list
                     .pop_back()
@@ -167,7 +247,8 @@ where
                 );
 
                 current_vertex = next_vertex;
-            } else {
+            }
+            _ => {
                 circuit.push_back(current_vertex);
                 current_vertex = curr_path
                     .pop_back()
@@ -176,7 +257,62 @@ where
         }
     }
 
-    let ordered_circuit: VecDeque<G::NodeId> = circuit.into_iter().rev().collect();
-    println!("{:?}", &ordered_circuit);
-    ordered_circuit
+    circuit.into_iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Graph = StableDiGraph<u32, f64, u32>;
+
+    //A triangle with input edges 0: 0-1, 1: 1-2 and 2: 2-0, laid out as `make_graph` does
+    fn triangle() -> (Graph, Vec<NodeIndex<u32>>) {
+        let mut graph = Graph::default();
+        let nodes: Vec<_> = (0..3).map(|label| graph.add_node(label)).collect();
+        for (u, v, w) in [(0, 1, 1.0), (1, 2, 2.0), (2, 0, 3.0)] {
+            graph.add_edge(nodes[u], nodes[v], w);
+            graph.add_edge(nodes[v], nodes[u], w);
+        }
+        (graph, nodes)
+    }
+
+    #[test]
+    fn walks_each_edge_once() {
+        let (graph, n) = triangle();
+        let mut egraph = EulerGraph::<Graph>::new();
+        egraph.edges = vec![(n[0], n[1]), (n[1], n[2]), (n[2], n[0])];
+
+        let circuit = make_euler_circuit::<Graph, u32>(&graph, &egraph, n[0]).unwrap();
+        let first = circuit.ordered_node_weight_list.first();
+        assert_eq!(first, circuit.ordered_node_weight_list.last());
+        assert_eq!(circuit.length, 6.0);
+        let mut ids: Vec<usize> = circuit.edge_list.iter().map(|edge| edge.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+        for (edge, (s, t)) in circuit.edge_list.iter().zip(&circuit.node_pair_list) {
+            let arc = crate::graph::input_edge_arc::<u32>(*edge);
+            assert_eq!(graph.edge_endpoints(arc), Some((*s, *t)));
+        }
+    }
+
+    #[test]
+    fn stays_at_the_source_without_edges() {
+        let (graph, n) = triangle();
+        let circuit =
+            make_euler_circuit::<Graph, u32>(&graph, &EulerGraph::<Graph>::new(), n[0]).unwrap();
+        assert_eq!(circuit.ordered_node_weight_list, vec![0]);
+        assert!(circuit.edge_list.is_empty());
+    }
+
+    #[test]
+    fn rejects_steps_that_are_not_edges() {
+        let (mut graph, n) = triangle();
+        let extra = graph.add_node(3);
+        let mut egraph = EulerGraph::<Graph>::new();
+        egraph.edges = vec![(n[0], extra), (extra, n[1]), (n[1], n[0])];
+
+        let error = make_euler_circuit::<Graph, u32>(&graph, &egraph, n[0]).unwrap_err();
+        assert!(error.to_string().contains("is not an edge"), "{}", error);
+    }
 }
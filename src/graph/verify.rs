@@ -0,0 +1,350 @@
+use crate::graph::{euler::WalkedEdge, input_edge, input_edge_arc};
+use crate::io::{CircuitSolution, SolutionDocument};
+
+use petgraph::{
+    stable_graph::{IndexType, NodeIndex, StableDiGraph},
+    visit::EdgeRef,
+};
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::Hash,
+    iter::Sum,
+};
+
+//Lengths are compared with a relative tolerance, as they are sums of floating point weights
+const LENGTH_TOLERANCE: f64 = 1e-9;
+
+/// A reason a circuit is not a valid closed walk of the graph
+///
+/// Positions index the circuit's node list; a step at position `i` runs from node `i` to node `i + 1`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CircuitProblem<N, E> {
+    Empty,
+    NotClosed {
+        first: N,
+        last: N,
+    },
+    UnknownNode {
+        position: usize,
+        node: N,
+    },
+    MissingEdge {
+        position: usize,
+        from: N,
+        to: N,
+    },
+    /// Every edge joining the step's nodes has already been walked earlier in the circuit
+    ReusedEdge {
+        position: usize,
+        from: N,
+        to: N,
+    },
+    /// The circuit records a different number of edges than it has steps
+    EdgeCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The edge recorded for the step does not lead from the step's first node to its second
+    EdgeMismatch {
+        position: usize,
+        edge: WalkedEdge<usize>,
+        from: N,
+        to: N,
+    },
+    LengthMismatch {
+        recorded: E,
+        actual: E,
+    },
+}
+
+impl<N: Debug, E: Debug> Display for CircuitProblem<N, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "circuit has no nodes"),
+            Self::NotClosed { first, last } => {
+                write!(f, "circuit starts at {:?} but ends at {:?}", first, last)
+            }
+            Self::UnknownNode { position, node } => {
+                write!(
+                    f,
+                    "node {:?} at position {} is not in the graph",
+                    node, position
+                )
+            }
+            Self::MissingEdge { position, from, to } => write!(
+                f,
+                "step {} from {:?} to {:?} is not an edge of the graph",
+                position, from, to
+            ),
+            Self::ReusedEdge { position, from, to } => write!(
+                f,
+                "step {} from {:?} to {:?} walks an edge already used",
+                position, from, to
+            ),
+            Self::EdgeCount { expected, found } => {
+                write!(f, "circuit records {} edges for {} steps", found, expected)
+            }
+            Self::EdgeMismatch {
+                position,
+                edge,
+                from,
+                to,
+            } => write!(
+                f,
+                "step {} from {:?} to {:?} records edge {} walked {}, which does not lead from one to the other",
+                position,
+                from,
+                to,
+                edge.id,
+                if edge.forward { "forward" } else { "backward" }
+            ),
+            Self::LengthMismatch { recorded, actual } => write!(
+                f,
+                "recorded length {:?} differs from actual length {:?}",
+                recorded, actual
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitReport<N, E> {
+    pub name: String,
+    /// Length of the circuit recomputed from the graph, over the steps that are edges
+    /// (counting reused edges each time they are walked)
+    pub length: E,
+    pub problems: Vec<CircuitProblem<N, E>>,
+}
+
+impl<N, E> CircuitReport<N, E> {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Maps each node label of a graph built by `make_graph` to its index
+pub fn node_label_index<N, E, Ix>(graph: &StableDiGraph<N, E, Ix>) -> HashMap<N, NodeIndex<Ix>>
+where
+    N: Eq + Hash + Copy,
+    Ix: IndexType,
+{
+    graph.node_indices().map(|ind| (graph[ind], ind)).collect()
+}
+
+/// Checks a circuit against a graph built by `make_graph` and recomputes its length
+///
+/// The circuit must be closed, each consecutive pair of nodes must be joined by an edge, and
+/// no undirected edge may be walked twice. When the circuit records its edges, each step must
+/// walk the recorded edge in the recorded direction; otherwise each step is taken to walk the
+/// shortest edge joining its nodes that is still unused. A recorded length must match the
+/// recomputed one.
+pub fn verify_circuit<N, E, Ix>(
+    graph: &StableDiGraph<N, E, Ix>,
+    node_indices: &HashMap<N, NodeIndex<Ix>>,
+    circuit: &CircuitSolution<N, E>,
+) -> CircuitReport<N, E>
+where
+    N: Eq + Hash + Copy,
+    E: Copy + PartialOrd + Sum + Into<f64>,
+    Ix: IndexType,
+{
+    let nodes = &circuit.nodes;
+    let mut problems = Vec::new();
+
+    match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) if first != last => problems.push(CircuitProblem::NotClosed {
+            first: *first,
+            last: *last,
+        }),
+        (Some(_), Some(_)) => {}
+        _ => problems.push(CircuitProblem::Empty),
+    }
+
+    let steps = nodes.len().saturating_sub(1);
+    let recorded_edges = if circuit.edges.is_empty() {
+        None
+    } else if circuit.edges.len() != steps {
+        problems.push(CircuitProblem::EdgeCount {
+            expected: steps,
+            found: circuit.edges.len(),
+        });
+        None
+    } else {
+        Some(&circuit.edges)
+    };
+
+    let resolved: Vec<Option<NodeIndex<Ix>>> = nodes
+        .iter()
+        .enumerate()
+        .map(|(position, node)| {
+            let ind = node_indices.get(node).copied();
+            if ind.is_none() {
+                problems.push(CircuitProblem::UnknownNode {
+                    position,
+                    node: *node,
+                });
+            }
+            ind
+        })
+        .collect();
+
+    let mut used = HashSet::<usize>::new();
+    let mut weights = Vec::<E>::new();
+    for (position, pair) in resolved.windows(2).enumerate() {
+        let (from, to) = (nodes[position], nodes[position + 1]);
+        let (Some(u), Some(v)) = (pair[0], pair[1]) else {
+            continue;
+        };
+
+        let (edge, weight) = match recorded_edges {
+            Some(edges) => {
+                let edge = edges[position];
+                let arc = input_edge_arc::<Ix>(edge);
+                match graph.edge_endpoints(arc) {
+                    Some(ends) if ends == (u, v) => {}
+                    _ => {
+                        problems.push(CircuitProblem::EdgeMismatch {
+                            position,
+                            edge,
+                            from,
+                            to,
+                        });
+                        continue;
+                    }
+                }
+                (edge.id, graph[arc])
+            }
+            None => {
+                let candidates: Vec<(usize, E)> = graph
+                    .edges_connecting(u, v)
+                    .map(|e| (input_edge(e.id()).id, *e.weight()))
+                    .collect();
+                let shortest = |(_, w1): &&(usize, E), (_, w2): &&(usize, E)| {
+                    w1.partial_cmp(w2).unwrap_or(Ordering::Equal)
+                };
+                match candidates
+                    .iter()
+                    .filter(|(edge, _)| !used.contains(edge))
+                    .min_by(shortest)
+                    .or_else(|| candidates.iter().min_by(shortest))
+                {
+                    Some(walked) => *walked,
+                    None => {
+                        problems.push(CircuitProblem::MissingEdge { position, from, to });
+                        continue;
+                    }
+                }
+            }
+        };
+
+        //A reused edge is still walked, so it counts towards the length
+        if !used.insert(edge) {
+            problems.push(CircuitProblem::ReusedEdge { position, from, to });
+        }
+        weights.push(weight);
+    }
+
+    let length: E = weights.into_iter().sum();
+    if let Some(recorded) = circuit.length {
+        let (r, a): (f64, f64) = (recorded.into(), length.into());
+        if (r - a).abs() > LENGTH_TOLERANCE * r.abs().max(1.0) {
+            problems.push(CircuitProblem::LengthMismatch {
+                recorded,
+                actual: length,
+            });
+        }
+    }
+
+    CircuitReport {
+        name: circuit.name.clone(),
+        length,
+        problems,
+    }
+}
+
+/// Checks every circuit of a solution document against a graph built by `make_graph`
+pub fn verify_solutions<N, E, Ix>(
+    graph: &StableDiGraph<N, E, Ix>,
+    document: &SolutionDocument<N, E>,
+) -> Vec<CircuitReport<N, E>>
+where
+    N: Eq + Hash + Copy,
+    E: Copy + PartialOrd + Sum + Into<f64>,
+    Ix: IndexType,
+{
+    let node_indices = node_label_index(graph);
+    document
+        .circuits
+        .iter()
+        .map(|circuit| verify_circuit(graph, &node_indices, circuit))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A triangle with input edges 0: 1-2, 1: 2-3 and 2: 3-1, laid out as `make_graph` does
+    fn triangle() -> StableDiGraph<u32, f64, u32> {
+        let mut graph = StableDiGraph::default();
+        let nodes: Vec<_> = (1..=3).map(|label| graph.add_node(label)).collect();
+        for (u, v) in [(0, 1), (1, 2), (2, 0)] {
+            graph.add_edge(nodes[u], nodes[v], 1.0);
+            graph.add_edge(nodes[v], nodes[u], 1.0);
+        }
+        graph
+    }
+
+    fn circuit(edges: &[(usize, bool)]) -> CircuitSolution<u32, f64> {
+        let mut circuit = CircuitSolution::from_nodes("upper", vec![1, 2, 3, 1]);
+        circuit.edges = edges
+            .iter()
+            .map(|&(id, forward)| WalkedEdge { id, forward })
+            .collect();
+        circuit.length = Some(3.0);
+        circuit
+    }
+
+    #[test]
+    fn checks_recorded_edges_and_directions() {
+        let graph = triangle();
+        let node_indices = node_label_index(&graph);
+
+        let report = verify_circuit(
+            &graph,
+            &node_indices,
+            &circuit(&[(0, true), (1, true), (2, true)]),
+        );
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert_eq!(report.length, 3.0);
+
+        //The step is left out of the length, so the recorded one no longer matches
+        let report = verify_circuit(
+            &graph,
+            &node_indices,
+            &circuit(&[(0, true), (1, false), (2, true)]),
+        );
+        assert!(matches!(
+            report.problems.as_slice(),
+            [
+                CircuitProblem::EdgeMismatch { position: 1, .. },
+                CircuitProblem::LengthMismatch { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn finds_reused_edges_without_recorded_ones() {
+        let graph = triangle();
+        let mut circuit = CircuitSolution::from_nodes("lower", vec![1, 2, 1]);
+        circuit.length = None;
+        let report = verify_circuit(&graph, &node_label_index(&graph), &circuit);
+        assert!(matches!(
+            report.problems.as_slice(),
+            [CircuitProblem::ReusedEdge { position: 1, .. }]
+        ));
+    }
+}
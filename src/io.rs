@@ -1,17 +1,97 @@
-use anyhow::{anyhow, Error};
-use itertools::Itertools;
+use anyhow::{anyhow, Context, Error};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs;
 use std::hash::Hash;
 
-use std::str::FromStr;
+mod attributes;
+mod cache;
+mod compression;
+#[cfg(feature = "csv")]
+mod csv;
+mod dimacs;
+mod dot;
+mod fit;
+mod geojson;
+mod geometry;
+#[cfg(feature = "geopackage")]
+mod geopackage;
+mod gpx;
+mod graphml;
+mod input;
+mod node_link;
+mod osm;
+mod polyline;
+#[cfg(feature = "shapefile")]
+mod shapefile;
+mod solution;
+mod wkt;
+
+pub use self::attributes::{Attributes, GraphAttributes};
+pub use self::cache::{
+    load_graph_cache, save_graph_cache, source_checksum, PreparedGraph, CACHE_VERSION,
+};
+pub use self::compression::open_input;
+#[cfg(feature = "csv")]
+pub use self::csv::{circuits_to_csv, read_from_csv, write_circuits_to_csv, CsvOptions};
+pub use self::dimacs::{
+    read_from_dimacs, read_from_dimacs_gr, write_to_dimacs_gr, DimacsCoOptions, DimacsError,
+    DimacsErrorKind,
+};
+pub use self::dot::{graph_to_dot, write_graph_to_dot, DotOptions};
+pub use self::fit::{
+    circuit_to_fit, encode_fit_course, write_circuit_to_fit, FitOptions, FitSport,
+};
+pub use self::geojson::{
+    circuits_to_geojson, read_from_geojson, read_from_geojson_file, write_circuits_to_geojson,
+    GeoJsonOptions,
+};
+pub use self::geometry::{circuit_positions, geodesic_length, Coordinate, EdgeGeometries};
+#[cfg(feature = "geopackage")]
+pub use self::geopackage::{
+    linestring_from_gpkg_blob, linestring_to_gpkg_blob, read_from_geopackage,
+    write_circuits_to_geopackage, GeoPackageOptions,
+};
+pub use self::gpx::{circuits_to_gpx, write_circuits_to_gpx, GpxKind, GpxOptions};
+#[cfg(feature = "xml")]
+pub use self::graphml::read_from_graphml;
+pub use self::graphml::{graph_to_graphml, write_graph_to_graphml, GraphMlOptions};
+pub use self::input::{detect_input_formats, read_graph, read_graph_as, InputFormat, InputOptions};
+pub use self::node_link::{
+    graph_to_node_link, read_from_node_link, read_from_node_link_file, write_graph_to_node_link,
+    NodeLinkOptions,
+};
+#[cfg(feature = "osm-pbf")]
+pub use self::osm::read_from_osm_pbf;
+#[cfg(feature = "xml")]
+pub use self::osm::read_from_osm_xml;
+pub use self::osm::{read_from_overpass_json, OsmTagFilter};
+pub use self::polyline::{
+    circuits_to_polyline, encode_polyline, write_circuits_to_polyline, PolylineOptions,
+};
+#[cfg(feature = "shapefile")]
+pub use self::shapefile::{read_from_shapefile, ShapefileOptions};
+pub use self::solution::{
+    read_solutions, solutions_to_json, write_solutions, CircuitSolution, SolutionDocument,
+    SolverParameters, SOLUTION_VERSION,
+};
+pub use self::wkt::{
+    circuits_to_wkt, linestring_from_wkt, linestring_to_wkt, write_circuits_to_wkt,
+};
 
 #[derive(Debug)]
 pub struct GraphRepresentation<N, E, Ix> {
     pub node_map: HashMap<Ix, N>,
     pub edge_list: Vec<(Ix, Ix, E)>,
+    //Only populated by readers whose input carries positions (e.g. GeoJSON)
+    pub coordinates: HashMap<Ix, Coordinate>,
+    //Only populated by readers whose input carries properties beyond the graph itself
+    pub node_attributes: HashMap<Ix, Attributes>,
+    /// One entry per edge of `edge_list`, or none at all
+    pub edge_attributes: Vec<Attributes>,
+    /// Shapes of the edges whose input gives them one, keyed by position in `edge_list`
+    pub edge_geometries: EdgeGeometries,
 }
 
 impl<N, E, Ix> GraphRepresentation<N, E, Ix> {
@@ -19,8 +99,75 @@ impl<N, E, Ix> GraphRepresentation<N, E, Ix> {
         GraphRepresentation {
             node_map: n,
             edge_list: e,
+            coordinates: HashMap::new(),
+            node_attributes: HashMap::new(),
+            edge_attributes: Vec::new(),
+            edge_geometries: HashMap::new(),
+        }
+    }
+
+    /// Coordinates keyed by node label rather than by index, as needed by the circuit writers
+    pub fn node_coordinates(&self) -> HashMap<N, Coordinate>
+    where
+        N: Eq + Hash + Copy,
+        Ix: Eq + Hash,
+    {
+        self.coordinates
+            .iter()
+            .filter_map(|(ind, coord)| self.node_map.get(ind).map(|node| (*node, *coord)))
+            .collect()
+    }
+
+    /// Attributes keyed by node label rather than by index, to be kept alongside the graph
+    /// built by `make_graph`
+    pub fn attributes(&self) -> GraphAttributes<N>
+    where
+        N: Eq + Hash + Copy,
+        Ix: Eq + Hash,
+    {
+        GraphAttributes {
+            nodes: self
+                .node_attributes
+                .iter()
+                .filter_map(|(ind, attrs)| {
+                    self.node_map.get(ind).map(|node| (*node, attrs.clone()))
+                })
+                .collect(),
+            edges: self.edge_attributes.clone(),
         }
     }
+
+    fn with_coordinates(mut self, c: HashMap<Ix, Coordinate>) -> GraphRepresentation<N, E, Ix> {
+        self.coordinates = c;
+        self
+    }
+
+    fn with_edge_geometries(mut self, g: EdgeGeometries) -> GraphRepresentation<N, E, Ix> {
+        self.edge_geometries = g;
+        self
+    }
+
+    fn with_attributes(
+        mut self,
+        nodes: HashMap<Ix, Attributes>,
+        edges: Vec<Attributes>,
+    ) -> GraphRepresentation<N, E, Ix> {
+        self.node_attributes = nodes;
+        self.edge_attributes = edges;
+        self
+    }
+}
+
+//The index of the next node added to `node_map`, for readers that number nodes as they
+//find them; fails once the index type cannot number any more
+fn next_node_index<N, Ix>(node_map: &HashMap<Ix, N>) -> Result<Ix, Error>
+where
+    Ix: TryFrom<u32>,
+{
+    u32::try_from(node_map.len())
+        .ok()
+        .and_then(|len| Ix::try_from(len).ok())
+        .ok_or_else(|| anyhow!("The graph has more nodes than the node index type can hold"))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,61 +176,22 @@ pub struct EdgeRepresentation<N, E> {
     pub start_node: N,
     pub end_node: N,
     pub weight: E,
+    /// WKT `LINESTRING` giving the edge's shape, from `start_node` to `end_node`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<String>,
+    /// Any other keys of the edge object
+    #[serde(flatten)]
+    pub attributes: Attributes,
 }
 
-pub fn read_from_dimacs<N, E, Ix>(filepath: &str) -> Result<GraphRepresentation<N, E, Ix>, Error>
-where
-    Ix: FromStr + Eq + Hash + TryFrom<u32>,
-    Ix::Err: Debug,
-    <Ix as TryFrom<u32>>::Error: Debug,
-    N: FromStr,
-    E: From<Ix>,
-{
-    let mut node_map = HashMap::<Ix, N>::new();
-    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
-    let mut nodes = Vec::<Vec<&str>>::new();
-    let mut edges = Vec::<Vec<&str>>::new();
-
-    if let Ok(content) = fs::read_to_string(filepath) {
-        let mut lines = content.lines();
-
-        let _size_tuple = lines.next();
-        lines.for_each(|line| match line.chars().next().expect("Empty line") {
-            'e' => edges.push(line.split_whitespace().skip(1).take(3).collect()),
-            'v' => nodes.push(line.split_whitespace().skip(1).collect()),
-            _ => {}
-        });
-
-        (0..).zip(nodes.into_iter()).for_each(|(i, vals)| {
-            if let Some(val) = vals.first().and_then(|val| val.parse::<N>().ok()) {
-                node_map.insert(Ix::try_from(i).unwrap(), val);
-            } else {
-                panic!("Missing node identifier")
-            }
-        });
-
-        edges.into_iter().for_each(|els| {
-            if let Some((u, v, w)) = els
-                .iter()
-                .map(|val| val.parse::<Ix>().unwrap())
-                .collect_tuple()
-            {
-                let w: E = w
-                    .try_into()
-                    .expect("Could not convert edge weight to signed int");
-                edge_list.push((u, v, w));
-            };
-        });
-
-        Ok(GraphRepresentation::new(node_map, edge_list))
-    } else {
-        return Err(anyhow!("Something"));
-    }
-}
-
+/// Reads a graph from a JSON array of `EdgeRepresentation`s, also returning the `edge_id`
+/// of each edge, in the same order as the graph's `edge_list`
+///
+/// An edge's `geometry` only shapes how circuits are drawn; its weight is used as given.
+#[allow(clippy::type_complexity)]
 pub fn read_from_edges_json<N, E, Ix>(
     json_string: String,
-) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Vec<N>), Error>
 where
     for<'de> N: Deserialize<'de>,
     for<'de> E: Deserialize<'de>,
@@ -92,49 +200,91 @@ where
     Ix: Eq + PartialEq + Hash + Copy + TryFrom<u32>,
     <Ix as TryFrom<u32>>::Error: Debug,
 {
-    println!("Beginning JSON parsing");
     //println!("{}", &json_string);
     if let Ok(edges_list) = serde_json::from_str::<Vec<EdgeRepresentation<N, E>>>(&json_string) {
-        println!("Deserialized JSON");
-        let mut node_weight_to_index = HashMap::<N, Ix>::new();
-        let mut edge_list = Vec::<(Ix, Ix, E)>::new();
-        let mut nodes = HashSet::<N>::new();
-        let mut edges = Vec::<(E, N, N)>::new();
-
-        edges_list.into_iter().for_each(|edge| {
-            nodes.insert(edge.start_node);
-            nodes.insert(edge.end_node);
-            if edge.start_node < edge.end_node {
-                edges.push((edge.weight, edge.start_node, edge.end_node));
-            } else {
-                edges.push((edge.weight, edge.start_node, edge.end_node));
-            }
-        });
+        edges_to_graph(edges_list)
+    } else {
+        Err(anyhow!("something"))
+    }
+}
 
-        (0..).zip(nodes.iter()).for_each(|(i, node_id)| {
-            if let Ok(ind) = Ix::try_from(i) {
-                node_weight_to_index.insert(*node_id, ind);
-            }
-        });
+#[allow(clippy::type_complexity)]
+fn edges_to_graph<N, E, Ix>(
+    edges_list: Vec<EdgeRepresentation<N, E>>,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Vec<N>), Error>
+where
+    N: PartialEq + PartialOrd + Eq + Hash + Copy,
+    Ix: Eq + PartialEq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    let mut nodes = HashSet::<N>::new();
+    let mut edges = Vec::<(E, N, N)>::new();
+    let mut ids = Vec::<(N, Option<String>, Attributes)>::new();
+    let mut edge_ids = Vec::<N>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+    let mut edge_geometries = EdgeGeometries::new();
 
-        edges.into_iter().for_each(|(w, u, v)| {
-            if let (Some(n1), Some(n2)) =
-                (node_weight_to_index.get(&u), node_weight_to_index.get(&v))
-            {
-                edge_list.push((*n1, *n2, w));
-            }
-        });
+    edges_list.into_iter().for_each(|edge| {
+        nodes.insert(edge.start_node);
+        nodes.insert(edge.end_node);
+        edges.push((edge.weight, edge.start_node, edge.end_node));
+        ids.push((edge.edge_id, edge.geometry, edge.attributes));
+    });
 
-        let node_map: HashMap<Ix, N> = node_weight_to_index.iter().map(|(k, v)| (*v, *k)).collect();
+    (0..).zip(nodes.iter()).for_each(|(i, node_id)| {
+        if let Ok(ind) = Ix::try_from(i) {
+            node_weight_to_index.insert(*node_id, ind);
+        }
+    });
 
-        return Ok((
-            GraphRepresentation::new(node_map, edge_list),
-            node_weight_to_index,
-        ));
-    } else {
-        return Err(anyhow!("something"));
+    for (i, ((w, u, v), (id, geometry, attrs))) in edges.into_iter().zip(ids).enumerate() {
+        if let (Some(n1), Some(n2)) = (node_weight_to_index.get(&u), node_weight_to_index.get(&v)) {
+            if let Some(wkt) = geometry {
+                let shape = linestring_from_wkt(&wkt)
+                    .map_err(|e| anyhow!("Edge {} has an invalid geometry: {}", i, e))?;
+                edge_geometries.insert(edge_list.len(), shape);
+            }
+            edge_list.push((*n1, *n2, w));
+            edge_ids.push(id);
+            edge_attributes.push(attrs);
+        }
     }
+
+    let node_map: HashMap<Ix, N> = node_weight_to_index.iter().map(|(k, v)| (*v, *k)).collect();
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_attributes(HashMap::new(), edge_attributes)
+            .with_edge_geometries(edge_geometries),
+        node_weight_to_index,
+        edge_ids,
+    ))
+}
+/// Reads a graph from a file holding a JSON array of `EdgeRepresentation`s, which may be
+/// gzip or zstd compressed, as `read_from_edges_json` does
+///
+/// The file is parsed as it is read, without first being read into memory whole.
+#[allow(clippy::type_complexity)]
+pub fn read_from_edges_json_file<N, E, Ix>(
+    path: &str,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Vec<N>), Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    N: PartialEq + PartialOrd + Eq + Hash + Copy,
+    (E, N, N): PartialEq + PartialOrd,
+    Ix: Eq + PartialEq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let edges_list = open_input(path)
+        .map_err(Error::from)
+        .and_then(|reader| Ok(serde_json::from_reader(reader)?))
+        .with_context(|| format!("Reading {}", path))?;
+    edges_to_graph(edges_list)
 }
+
 pub fn write_solution_strings_to_file(
     path: &str,
     solution_string: String,
@@ -0,0 +1,52 @@
+use crate::graph::euler::WalkedEdge;
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The properties of a node or edge in its input file that are not part of the graph itself,
+/// such as names, surface types or OSM tags
+pub type Attributes = Map<String, Value>;
+
+/// The attributes of a graph's nodes, keyed by node label, and of its edges
+///
+/// Edges are indexed by their position in the input edge list, as are the ids of
+/// `WalkedEdge`s, so the attributes stay valid for a graph built by `make_graph`
+/// however it is later trimmed.
+#[derive(Debug, Clone)]
+pub struct GraphAttributes<N> {
+    pub nodes: HashMap<N, Attributes>,
+    /// One entry per input edge, or none at all when the input carries no edge attributes
+    pub edges: Vec<Attributes>,
+}
+
+impl<N> Default for GraphAttributes<N> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<N> GraphAttributes<N> {
+    pub fn node(&self, node: &N) -> Option<&Attributes>
+    where
+        N: Eq + Hash,
+    {
+        self.nodes.get(node)
+    }
+
+    pub fn edge(&self, edge: usize) -> Option<&Attributes> {
+        self.edges.get(edge)
+    }
+
+    /// The attributes of each edge a circuit walks, in circuit order; edges without
+    /// attributes get an empty set
+    pub fn walked_edges(&self, edges: &[WalkedEdge<usize>]) -> Vec<Attributes> {
+        edges
+            .iter()
+            .map(|edge| self.edge(edge.id).cloned().unwrap_or_default())
+            .collect()
+    }
+}
@@ -0,0 +1,392 @@
+use super::geometry::{Coordinate, EdgeGeometries};
+use super::input::companion_paths;
+use super::{Attributes, GraphAttributes, InputOptions};
+
+use anyhow::{anyhow, Context, Error};
+use bincode::Options;
+use petgraph::stable_graph::{IndexType, NodeIndex, StableDiGraph};
+use serde::{Deserialize, Serialize};
+use std::any::type_name;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+const CACHE_MAGIC: &[u8; 8] = b"SPDCYGRF";
+/// Bumped whenever the layout of the cache changes; caches of other versions are rebuilt
+pub const CACHE_VERSION: u32 = 4;
+
+/// A graph as built by `make_graph`, along with everything needed to route on it
+/// without going back to its source file
+#[derive(Debug)]
+pub struct PreparedGraph<N, E, Ix: IndexType> {
+    pub graph: StableDiGraph<N, E, Ix>,
+    /// Maps each input node index (as used for the source vertex) to its node in `graph`
+    pub node_index_mapper: HashMap<Ix, NodeIndex<Ix>>,
+    pub coordinates: HashMap<N, Coordinate>,
+    pub attributes: GraphAttributes<N>,
+    pub edge_geometries: EdgeGeometries,
+}
+
+//Coordinate skips absent elevations when serialized, which a non-self-describing
+//format cannot read back, so positions are cached as plain (lon, lat, elevation) tuples
+type CachedCoordinate<N> = (N, f64, f64, Option<f64>);
+//Edge shapes are cached the same way, keyed by edge
+type CachedShape = (usize, Vec<(f64, f64, Option<f64>)>);
+
+//Attribute values are arbitrary JSON, which bincode cannot decode either, so the
+//attributes are cached as a JSON string
+type CachedAttributes<N> = (Vec<(N, Attributes)>, Vec<Attributes>);
+
+//The encoding `bincode::serialize_into` writes, refusing to read more than `limit` bytes
+//so that a corrupt length prefix fails to decode rather than exhausting memory
+fn decoding_options(limit: u64) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+}
+
+//Everything that must match for a cache to be used in place of its source file
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CacheHeader {
+    version: u32,
+    source_length: u64,
+    source_checksum: u64,
+    //The lengths and checksums of the other files read along with the source, such as its
+    //`.co` coordinate file
+    companions: Vec<(u64, u64)>,
+    //The reader options change the graph read from the same files
+    options_checksum: u64,
+    //Caches are only readable with the node, edge and index types they were written with
+    types: String,
+}
+
+impl CacheHeader {
+    fn for_source<N, E, Ix>(source_path: &str, options: &InputOptions) -> Result<Self, Error> {
+        let (source_length, source_checksum) = source_checksum(source_path)?;
+        let companions = companion_paths(source_path, options)
+            .iter()
+            .map(|path| source_checksum_of(path))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            version: CACHE_VERSION,
+            source_length,
+            source_checksum,
+            companions,
+            //Every options type holds only plain values and `Vec`s, so its `Debug` output is
+            //the same from run to run
+            options_checksum: xxh3_64(format!("{:?}", options).as_bytes()),
+            types: format!(
+                "{}, {}, {}",
+                type_name::<N>(),
+                type_name::<E>(),
+                type_name::<Ix>()
+            ),
+        })
+    }
+}
+
+/// The length and XXH3 checksum of a file, read as a stream
+pub fn source_checksum(path: &str) -> Result<(u64, u64), Error> {
+    source_checksum_of(Path::new(path))
+}
+
+fn source_checksum_of(path: &Path) -> Result<(u64, u64), Error> {
+    let mut file = File::open(path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; 1 << 16];
+    let mut length = 0;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        length += read as u64;
+    }
+    Ok((length, hasher.digest()))
+}
+
+/// Writes a prepared graph to `cache_path`, recording checksums of the files it was built from
+/// and of the reader options it was read with
+///
+/// The cache is written to a temporary file beside `cache_path` and then moved into place, so
+/// an interrupted write never leaves a partial cache behind.
+pub fn save_graph_cache<N, E, Ix>(
+    cache_path: &str,
+    source_path: &str,
+    options: &InputOptions,
+    prepared: &PreparedGraph<N, E, Ix>,
+) -> Result<(), Error>
+where
+    N: Serialize,
+    E: Serialize,
+    Ix: IndexType + Serialize,
+{
+    let header = CacheHeader::for_source::<N, E, Ix>(source_path, options)?;
+    let cache_dir = Path::new(cache_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut temp_file =
+        NamedTempFile::new_in(cache_dir).with_context(|| format!("Writing {}", cache_path))?;
+    let mut writer = BufWriter::new(temp_file.as_file_mut());
+    let coordinates: Vec<CachedCoordinate<&N>> = prepared
+        .coordinates
+        .iter()
+        .map(|(node, c)| (node, c.lon, c.lat, c.elevation))
+        .collect();
+    let edge_geometries: Vec<CachedShape> = prepared
+        .edge_geometries
+        .iter()
+        .map(|(edge, shape)| {
+            (
+                *edge,
+                shape.iter().map(|c| (c.lon, c.lat, c.elevation)).collect(),
+            )
+        })
+        .collect();
+    let attributes: CachedAttributes<&N> = (
+        prepared
+            .attributes
+            .nodes
+            .iter()
+            .map(|(node, attrs)| (node, attrs.clone()))
+            .collect(),
+        prepared.attributes.edges.clone(),
+    );
+
+    writer.write_all(CACHE_MAGIC)?;
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(
+        &mut writer,
+        &(
+            &prepared.graph,
+            &prepared.node_index_mapper,
+            coordinates,
+            serde_json::to_string(&attributes)?,
+            edge_geometries,
+        ),
+    )?;
+    writer.flush()?;
+    drop(writer);
+    temp_file
+        .persist(cache_path)
+        .map_err(|e| e.error)
+        .with_context(|| format!("Writing {}", cache_path))?;
+    Ok(())
+}
+
+/// Loads the prepared graph cached at `cache_path` for `source_path`, read with `options`
+///
+/// Returns `None` when there is no cache yet, or when it is stale: written from a different
+/// version of the source file or of any file read along with it, with different reader
+/// options, by a different cache version, or with different node, edge or index types. A file that is not a cache at all is an error, so that it is never
+/// mistaken for one and overwritten.
+pub fn load_graph_cache<N, E, Ix>(
+    cache_path: &str,
+    source_path: &str,
+    options: &InputOptions,
+) -> Result<Option<PreparedGraph<N, E, Ix>>, Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    N: Eq + Hash,
+    Ix: IndexType + for<'de> Deserialize<'de>,
+{
+    let mut reader = match File::open(cache_path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::from(e).context(format!("Reading {}", cache_path))),
+    };
+    //Nothing in the cache can take up more than the whole file
+    let limit = reader
+        .get_ref()
+        .metadata()
+        .with_context(|| format!("Reading {}", cache_path))?
+        .len();
+
+    let mut magic = [0; 8];
+    reader
+        .read_exact(&mut magic)
+        .ok()
+        .filter(|_| &magic == CACHE_MAGIC)
+        .ok_or_else(|| anyhow!("{} is not a speedicycle graph cache", cache_path))?;
+
+    //A header from another cache version may not even decode, which also makes it stale
+    match decoding_options(limit).deserialize_from::<_, CacheHeader>(&mut reader) {
+        Ok(header) if header == CacheHeader::for_source::<N, E, Ix>(source_path, options)? => {}
+        _ => return Ok(None),
+    }
+
+    let (graph, node_index_mapper, coordinates, attributes, edge_geometries): (
+        _,
+        _,
+        Vec<CachedCoordinate<N>>,
+        String,
+        Vec<CachedShape>,
+    ) = decoding_options(limit)
+        .deserialize_from(&mut reader)
+        .with_context(|| format!("Decoding {}", cache_path))?;
+    let (node_attributes, edge_attributes): CachedAttributes<N> =
+        serde_json::from_str(&attributes).with_context(|| format!("Decoding {}", cache_path))?;
+    let coordinate = |lon, lat, elevation| Coordinate {
+        lon,
+        lat,
+        elevation,
+    };
+    Ok(Some(PreparedGraph {
+        graph,
+        node_index_mapper,
+        coordinates: coordinates
+            .into_iter()
+            .map(|(node, lon, lat, elevation)| (node, coordinate(lon, lat, elevation)))
+            .collect(),
+        attributes: GraphAttributes {
+            nodes: node_attributes.into_iter().collect(),
+            edges: edge_attributes,
+        },
+        edge_geometries: edge_geometries
+            .into_iter()
+            .map(|(edge, shape)| {
+                (
+                    edge,
+                    shape
+                        .into_iter()
+                        .map(|(lon, lat, elevation)| coordinate(lon, lat, elevation))
+                        .collect(),
+                )
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn prepared() -> PreparedGraph<u32, f64, u32> {
+        let mut graph = StableDiGraph::<u32, f64, u32>::default();
+        let a = graph.add_node(10);
+        let b = graph.add_node(11);
+        graph.add_edge(a, b, 2.5);
+        graph.add_edge(b, a, 2.5);
+        PreparedGraph {
+            graph,
+            node_index_mapper: HashMap::from([(0, a), (1, b)]),
+            coordinates: HashMap::from([(10, Coordinate::new(-73.9, 40.7).with_elevation(3.0))]),
+            attributes: GraphAttributes::default(),
+            edge_geometries: EdgeGeometries::new(),
+        }
+    }
+
+    fn options() -> InputOptions {
+        InputOptions::default()
+    }
+
+    fn paths(dir: &TempDir) -> (String, String) {
+        let source = dir.path().join("graph.txt");
+        std::fs::write(&source, "p 2 1\nv 10\nv 11\ne 0 1 2.5\n").unwrap();
+        (
+            dir.path().join("graph.cache").to_str().unwrap().to_string(),
+            source.to_str().unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn round_trips_a_prepared_graph() {
+        let dir = TempDir::new().unwrap();
+        let (cache_path, source_path) = paths(&dir);
+        save_graph_cache(&cache_path, &source_path, &options(), &prepared()).unwrap();
+
+        let loaded = load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options())
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.graph.edge_count(), 2);
+        assert_eq!(loaded.node_index_mapper, prepared().node_index_mapper);
+        assert_eq!(loaded.coordinates, prepared().coordinates);
+    }
+
+    #[test]
+    fn rejects_lengths_beyond_the_end_of_the_file() {
+        let dir = TempDir::new().unwrap();
+        let (cache_path, source_path) = paths(&dir);
+        let mut bytes = CACHE_MAGIC.to_vec();
+        bincode::serialize_into(
+            &mut bytes,
+            &CacheHeader::for_source::<u32, f64, u32>(&source_path, &options()).unwrap(),
+        )
+        .unwrap();
+        //The graph's node list claims far more entries than the file could hold
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&cache_path, bytes).unwrap();
+
+        let err =
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Decoding"));
+    }
+
+    #[test]
+    fn treats_caches_of_other_sources_as_stale() {
+        let dir = TempDir::new().unwrap();
+        let (cache_path, source_path) = paths(&dir);
+        save_graph_cache(&cache_path, &source_path, &options(), &prepared()).unwrap();
+        std::fs::write(&source_path, "p 2 1\nv 10\nv 11\ne 0 1 3\n").unwrap();
+
+        assert!(
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn treats_caches_read_with_other_companions_or_options_as_stale() {
+        let dir = TempDir::new().unwrap();
+        let (cache_path, source_path) = paths(&dir);
+        let co_path = dir.path().join("graph.co");
+        std::fs::write(&co_path, "p aux sp co 2\nv 1 -73985700 40748400\n").unwrap();
+        save_graph_cache(&cache_path, &source_path, &options(), &prepared()).unwrap();
+        assert!(
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options())
+                .unwrap()
+                .is_some()
+        );
+
+        std::fs::write(&co_path, "p aux sp co 2\nv 1 -73985700 40748500\n").unwrap();
+        assert!(
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options())
+                .unwrap()
+                .is_none()
+        );
+
+        save_graph_cache(&cache_path, &source_path, &options(), &prepared()).unwrap();
+        let mut snapped = options();
+        snapped.geojson.snap_tolerance *= 2.0;
+        assert!(
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &snapped)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn replaces_the_cache_without_leaving_temporary_files() {
+        let dir = TempDir::new().unwrap();
+        let (cache_path, source_path) = paths(&dir);
+        std::fs::write(&cache_path, "an older cache").unwrap();
+        save_graph_cache(&cache_path, &source_path, &options(), &prepared()).unwrap();
+
+        assert!(
+            load_graph_cache::<u32, f64, u32>(&cache_path, &source_path, &options())
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
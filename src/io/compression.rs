@@ -0,0 +1,108 @@
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+//Compressed input met by a build without the feature that decompresses it
+#[cfg(not(all(feature = "gzip", feature = "zstd")))]
+fn disabled(compression: &str, feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} compressed input requires speedicycle's `{}` feature",
+            compression, feature
+        ),
+    )
+}
+
+/// Opens a file for reading, decompressing it as it is read when it starts with the gzip or
+/// zstd magic bytes, whatever its extension
+pub fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let head = reader.fill_buf()?;
+
+    if head.starts_with(GZIP_MAGIC) {
+        //Concatenated gzip members are read as one stream, as `gzip -d` does
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+        #[cfg(not(feature = "gzip"))]
+        return Err(disabled("gzip", "gzip"));
+    }
+    if head.starts_with(ZSTD_MAGIC) {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(BufReader::new(ZstdDecoder::with_buffer(reader)?)));
+        #[cfg(not(feature = "zstd"))]
+        return Err(disabled("zstd", "zstd"));
+    }
+    Ok(Box::new(reader))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "gzip")]
+    use flate2::write::GzEncoder;
+    #[cfg(feature = "gzip")]
+    use flate2::Compression;
+    use std::io::{Read, Write};
+    use tempfile::NamedTempFile;
+
+    const CONTENTS: &str = "p 2 1\nv 10\nv 11\ne 0 1 2.5\n";
+
+    fn write_file(bytes: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        file
+    }
+
+    #[cfg(feature = "gzip")]
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read(file: &NamedTempFile) -> String {
+        let mut contents = String::new();
+        open_input(file.path().to_str().unwrap())
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn reads_plain_files_unchanged() {
+        assert_eq!(read(&write_file(CONTENTS.as_bytes())), CONTENTS);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn decompresses_gzip_files() {
+        let compressed = gzip(CONTENTS);
+        assert!(compressed.starts_with(GZIP_MAGIC));
+        assert_eq!(read(&write_file(&compressed)), CONTENTS);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn decompresses_concatenated_gzip_members() {
+        let (first, rest) = CONTENTS.split_at(9);
+        let mut compressed = gzip(first);
+        compressed.extend(gzip(rest));
+        assert_eq!(read(&write_file(&compressed)), CONTENTS);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decompresses_zstd_files() {
+        let compressed = zstd::encode_all(CONTENTS.as_bytes(), 3).unwrap();
+        assert!(compressed.starts_with(ZSTD_MAGIC));
+        assert_eq!(read(&write_file(&compressed)), CONTENTS);
+    }
+}
@@ -0,0 +1,416 @@
+use super::compression::open_input;
+use super::geometry::Coordinate;
+use super::{next_node_index, Attributes, GraphRepresentation};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::{anyhow, Context, Error};
+use csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::fs;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Column names and delimiter shared by the CSV nodes file, edges file and circuit output
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub node_id: String,
+    /// Nodes are only given coordinates when both the latitude and longitude columns are present
+    pub latitude: String,
+    pub longitude: String,
+    /// Optional; an empty cell leaves the node without an elevation
+    pub elevation: String,
+    pub edge_from: String,
+    pub edge_to: String,
+    pub edge_length: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            node_id: "id".to_string(),
+            latitude: "lat".to_string(),
+            longitude: "lon".to_string(),
+            elevation: "elevation".to_string(),
+            edge_from: "from".to_string(),
+            edge_to: "to".to_string(),
+            edge_length: "length".to_string(),
+        }
+    }
+}
+
+struct CsvTable {
+    path: String,
+    headers: StringRecord,
+    records: Vec<StringRecord>,
+}
+
+impl CsvTable {
+    fn read(path: &str, options: &CsvOptions) -> Result<Self, Error> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .trim(Trim::All)
+            .from_reader(open_input(path).with_context(|| format!("Reading {}", path))?);
+        let headers = reader
+            .headers()
+            .with_context(|| format!("Reading {}", path))?
+            .clone();
+        let records = reader
+            .records()
+            .collect::<Result<Vec<StringRecord>, csv::Error>>()
+            .with_context(|| format!("Reading {}", path))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            headers,
+            records,
+        })
+    }
+
+    fn column(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|header| header == name)
+    }
+
+    fn required_column(&self, name: &str) -> Result<usize, Error> {
+        self.column(name)
+            .ok_or_else(|| anyhow!("{}: missing `{}` column", self.path, name))
+    }
+
+    fn parse<T: FromStr>(&self, record: &StringRecord, column: usize) -> Result<T, Error> {
+        let cell = record.get(column).unwrap_or_default();
+        cell.parse::<T>().map_err(|_| {
+            anyhow!(
+                "{}:{}: invalid `{}` value `{}`",
+                self.path,
+                record.position().map_or(0, |pos| pos.line()),
+                &self.headers[column],
+                cell
+            )
+        })
+    }
+
+    //Every cell of the record whose column is not one of `used`, keyed by column name
+    fn remaining(&self, record: &StringRecord, used: &[usize]) -> Attributes {
+        self.headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .filter(|(i, _)| !used.contains(i))
+            .map(|(_, (header, cell))| (header.to_string(), cell.into()))
+            .collect()
+    }
+}
+
+/// Reads a graph from a CSV edges file and, optionally, a CSV nodes file
+///
+/// Each edge row joins the nodes named in its `from` and `to` columns with a weight taken
+/// from its `length` column. When a nodes file is given every edge endpoint must appear in
+/// it, and nodes are indexed in file order; otherwise nodes are indexed as edges first name
+/// them. All other columns are kept, as strings, as node and edge attributes.
+#[allow(clippy::type_complexity)]
+pub fn read_from_csv<N, E, Ix>(
+    nodes_path: Option<&str>,
+    edges_path: &str,
+    options: &CsvOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: FromStr + Eq + Hash + Copy + Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+    let mut node_attributes = HashMap::<Ix, Attributes>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+
+    if let Some(nodes_path) = nodes_path {
+        let table = CsvTable::read(nodes_path, options)?;
+        let id_column = table.required_column(&options.node_id)?;
+        let position_columns = table
+            .column(&options.latitude)
+            .zip(table.column(&options.longitude));
+        let elevation_column = table.column(&options.elevation);
+        let used: Vec<usize> = [Some(id_column), elevation_column]
+            .into_iter()
+            .flatten()
+            .chain(
+                position_columns
+                    .into_iter()
+                    .flat_map(|(lat, lon)| [lat, lon]),
+            )
+            .collect();
+
+        for record in &table.records {
+            let node_id = table.parse::<N>(record, id_column)?;
+            if node_weight_to_index.contains_key(&node_id) {
+                return Err(anyhow!(
+                    "{}:{}: duplicate node id {:?}",
+                    nodes_path,
+                    record.position().map_or(0, |pos| pos.line()),
+                    node_id
+                ));
+            }
+            let ind = next_node_index(&node_map)?;
+
+            if let Some((lat_column, lon_column)) = position_columns {
+                //Nodes with blank positions are kept, just without coordinates
+                if !record[lat_column].is_empty() || !record[lon_column].is_empty() {
+                    let mut coord = Coordinate::new(
+                        table.parse(record, lon_column)?,
+                        table.parse(record, lat_column)?,
+                    );
+                    if let Some(column) = elevation_column.filter(|c| !record[*c].is_empty()) {
+                        coord = coord.with_elevation(table.parse(record, column)?);
+                    }
+                    coordinates.insert(ind, coord);
+                }
+            }
+
+            node_map.insert(ind, node_id);
+            node_weight_to_index.insert(node_id, ind);
+            node_attributes.insert(ind, table.remaining(record, &used));
+        }
+    }
+
+    let table = CsvTable::read(edges_path, options)?;
+    let from_column = table.required_column(&options.edge_from)?;
+    let to_column = table.required_column(&options.edge_to)?;
+    let length_column = table.required_column(&options.edge_length)?;
+    let used = [from_column, to_column, length_column];
+
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    for record in &table.records {
+        let mut index_of = |column: usize| -> Result<Ix, Error> {
+            let node_id = table.parse::<N>(record, column)?;
+            if let Some(ind) = node_weight_to_index.get(&node_id) {
+                return Ok(*ind);
+            }
+            if nodes_path.is_some() {
+                return Err(anyhow!(
+                    "{}:{}: node {:?} is not in the nodes file",
+                    edges_path,
+                    record.position().map_or(0, |pos| pos.line()),
+                    node_id
+                ));
+            }
+            let ind = next_node_index(&node_map)?;
+            node_map.insert(ind, node_id);
+            node_weight_to_index.insert(node_id, ind);
+            Ok(ind)
+        };
+        let u = index_of(from_column)?;
+        let v = index_of(to_column)?;
+        let w = table.parse::<f64>(record, length_column)?;
+
+        edge_list.push((u, v, E::from(w)));
+        edge_attributes.push(table.remaining(record, &used));
+    }
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(node_attributes, edge_attributes),
+        node_weight_to_index,
+    ))
+}
+
+/// Renders circuits as CSV, with one row per visited node in circuit order
+///
+/// Rows carry the circuit name, the position within the circuit and the node id, followed
+/// by the node's position under the configured column names; position cells are left
+/// empty for nodes without coordinates.
+pub fn circuits_to_csv<G>(
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    options: &CsvOptions,
+) -> Result<String, Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Display,
+{
+    let mut writer = WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(Vec::new());
+    writer.write_record([
+        "circuit",
+        "sequence",
+        &options.node_id,
+        &options.latitude,
+        &options.longitude,
+        &options.elevation,
+    ])?;
+
+    for (name, circuit) in circuits {
+        for (i, node) in circuit.ordered_node_weight_list.iter().enumerate() {
+            let coord = coordinates.get(node);
+            writer.write_record([
+                name.to_string(),
+                i.to_string(),
+                node.to_string(),
+                coord.map(|c| c.lat.to_string()).unwrap_or_default(),
+                coord.map(|c| c.lon.to_string()).unwrap_or_default(),
+                coord
+                    .and_then(|c| c.elevation)
+                    .map(|e| e.to_string())
+                    .unwrap_or_default(),
+            ])?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+pub fn write_circuits_to_csv<G>(
+    path: &str,
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    options: &CsvOptions,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Display,
+{
+    fs::write(path, circuits_to_csv(circuits, coordinates, options)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::stable_graph::StableDiGraph;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn read(
+        nodes: Option<&str>,
+        edges: &str,
+        options: &CsvOptions,
+    ) -> Result<GraphRepresentation<u64, f64, u32>, Error> {
+        let dir = TempDir::new().unwrap();
+        let nodes_path = nodes.map(|nodes| write(&dir, "nodes.csv", nodes));
+        let edges_path = write(&dir, "edges.csv", edges);
+        read_from_csv(nodes_path.as_deref(), &edges_path, options).map(|(gr, _)| gr)
+    }
+
+    #[test]
+    fn maps_configured_columns_and_keeps_the_rest_as_attributes() {
+        let options = CsvOptions {
+            delimiter: b';',
+            node_id: "osmid".to_string(),
+            edge_from: "u".to_string(),
+            edge_to: "v".to_string(),
+            edge_length: "meters".to_string(),
+            ..Default::default()
+        };
+        let gr = read(
+            Some("osmid;lat;lon;elevation;kind\n7;40.7;-73.9;12;junction\n9;40.701;-73.9;;\n"),
+            "name; u; v; meters\nMain;9;7;111.5\n",
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(gr.node_map, HashMap::from([(0, 7), (1, 9)]));
+        assert_eq!(gr.edge_list, vec![(1, 0, 111.5)]);
+        assert_eq!(
+            gr.edge_attributes[0],
+            Attributes::from_iter([("name".into(), "Main".into())])
+        );
+        assert_eq!(gr.node_attributes[&0]["kind"], "junction");
+        assert_eq!(gr.node_attributes[&1]["kind"], "");
+        assert_eq!(
+            gr.coordinates[&0],
+            Coordinate::new(-73.9, 40.7).with_elevation(12.0)
+        );
+        assert_eq!(gr.coordinates[&1], Coordinate::new(-73.9, 40.701));
+    }
+
+    #[test]
+    fn numbers_nodes_as_edges_name_them_without_a_nodes_file() {
+        let gr = read(
+            None,
+            "from,to,length\n5,3,1\n3,8,2.5\n",
+            &CsvOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(gr.node_map, HashMap::from([(0, 5), (1, 3), (2, 8)]));
+        assert_eq!(gr.edge_list, vec![(0, 1, 1.0), (1, 2, 2.5)]);
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_weights() {
+        let missing_column = read(None, "from,to\n1,2\n", &CsvOptions::default()).unwrap_err();
+        assert!(missing_column
+            .to_string()
+            .contains("missing `length` column"));
+
+        let empty = read(None, "from,to,length\n1,2,\n", &CsvOptions::default()).unwrap_err();
+        assert!(empty.to_string().ends_with(":2: invalid `length` value ``"));
+
+        let text = read(None, "from,to,length\n1,2,far\n", &CsvOptions::default()).unwrap_err();
+        assert!(text
+            .to_string()
+            .ends_with(":2: invalid `length` value `far`"));
+    }
+
+    #[test]
+    fn rejects_edges_to_nodes_missing_from_the_nodes_file() {
+        let err = read(
+            Some("id\n1\n2\n"),
+            "from,to,length\n1,3,1\n",
+            &CsvOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with(":2: node 3 is not in the nodes file"));
+    }
+
+    #[test]
+    fn writes_circuits_that_read_back_in_order() {
+        let circuit = EulerCircuit::<StableDiGraph<u64, f64, u32>> {
+            length: 3.0,
+            ordered_node_weight_list: vec![7, 9, 7],
+            ..Default::default()
+        };
+        let coordinates = HashMap::from([(7, Coordinate::new(-73.9, 40.7).with_elevation(12.0))]);
+        let options = CsvOptions {
+            delimiter: b'\t',
+            ..Default::default()
+        };
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("circuits.tsv");
+        write_circuits_to_csv(
+            path.to_str().unwrap(),
+            &[("upper", &circuit)],
+            &coordinates,
+            &options,
+        )
+        .unwrap();
+
+        let mut reader = ReaderBuilder::new()
+            .delimiter(b'\t')
+            .from_path(&path)
+            .unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["circuit", "sequence", "id", "lat", "lon", "elevation"]
+        );
+        let rows: Vec<StringRecord> = reader.records().map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], vec!["upper", "0", "7", "40.7", "-73.9", "12"]);
+        assert_eq!(rows[1], vec!["upper", "1", "9", "", "", ""]);
+        assert_eq!(rows[2], vec!["upper", "2", "7", "40.7", "-73.9", "12"]);
+    }
+}
@@ -0,0 +1,804 @@
+use super::compression::open_input;
+use super::geometry::Coordinate;
+use super::GraphRepresentation;
+
+use anyhow::{anyhow, Context, Error};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Write};
+use std::fs;
+use std::hash::Hash;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// Controls how positions in a DIMACS `.co` coordinate file are read and written
+#[derive(Debug, Clone)]
+pub struct DimacsCoOptions {
+    /// Units per degree of the `x y` positions, which are longitude and latitude; the
+    /// challenge's road networks store integer millionths of a degree
+    pub scale: f64,
+}
+
+impl Default for DimacsCoOptions {
+    fn default() -> Self {
+        Self { scale: 1_000_000.0 }
+    }
+}
+
+#[derive(Debug)]
+pub enum DimacsErrorKind {
+    Io(std::io::Error),
+    MissingHeader,
+    DuplicateHeader,
+    /// A line whose leading token is not one the format allows
+    UnexpectedLine(String),
+    MissingField(&'static str),
+    InvalidField(&'static str, String),
+    UnexpectedField(String),
+    /// An edge or coordinate refers to a vertex the header does not account for
+    VertexOutOfRange {
+        vertex: u64,
+        count: usize,
+    },
+    /// The header declares more vertices than the index type can address
+    TooManyVertices(usize),
+    /// The header's vertex or edge count differs from the number actually read
+    CountMismatch {
+        what: &'static str,
+        declared: usize,
+        found: usize,
+    },
+}
+
+impl Display for DimacsErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::MissingHeader => write!(f, "missing `p` header line"),
+            Self::DuplicateHeader => write!(f, "more than one `p` header line"),
+            Self::UnexpectedLine(token) => write!(f, "unexpected line type `{}`", token),
+            Self::MissingField(what) => write!(f, "missing {}", what),
+            Self::InvalidField(what, token) => write!(f, "invalid {} `{}`", what, token),
+            Self::UnexpectedField(token) => write!(f, "unexpected trailing field `{}`", token),
+            Self::VertexOutOfRange { vertex, count } => write!(
+                f,
+                "vertex {} is out of range for a graph of {} vertices",
+                vertex, count
+            ),
+            Self::TooManyVertices(count) => {
+                write!(
+                    f,
+                    "{} vertices cannot be addressed by the index type",
+                    count
+                )
+            }
+            Self::CountMismatch {
+                what,
+                declared,
+                found,
+            } => write!(
+                f,
+                "header declares {} {} but {} were found",
+                declared, what, found
+            ),
+        }
+    }
+}
+
+/// A DIMACS parsing failure, located by file path and, where it applies, 1-based line and column
+#[derive(Debug)]
+pub struct DimacsError {
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub kind: DimacsErrorKind,
+}
+
+impl Display for DimacsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}", self.path, line, column, self.kind)
+            }
+            (Some(line), None) => write!(f, "{}:{}: {}", self.path, line, self.kind),
+            _ => write!(f, "{}: {}", self.path, self.kind),
+        }
+    }
+}
+
+impl std::error::Error for DimacsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            DimacsErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl DimacsError {
+    fn file(path: &str, kind: DimacsErrorKind) -> Self {
+        Self {
+            path: path.to_string(),
+            line: None,
+            column: None,
+            kind,
+        }
+    }
+}
+
+//Walks the whitespace-separated fields of one line, remembering where each starts so that
+//errors can point at the offending column
+struct Fields<'a> {
+    path: &'a str,
+    line: usize,
+    end_column: usize,
+    tokens: std::vec::IntoIter<(usize, &'a str)>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(path: &'a str, line_number: usize, line: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut start: Option<usize> = None;
+        for (i, c) in line.char_indices() {
+            match (c.is_whitespace(), start) {
+                (true, Some(s)) => {
+                    tokens.push((s, &line[s..i]));
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            tokens.push((s, &line[s..]));
+        }
+
+        let column_of = |byte: usize| line[..byte].chars().count() + 1;
+        Self {
+            path,
+            line: line_number,
+            end_column: column_of(line.len()),
+            tokens: tokens
+                .into_iter()
+                .map(|(s, token)| (column_of(s), token))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        }
+    }
+
+    fn error(&self, column: usize, kind: DimacsErrorKind) -> DimacsError {
+        DimacsError {
+            path: self.path.to_string(),
+            line: Some(self.line),
+            column: Some(column),
+            kind,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<(usize, &'a str)> {
+        self.tokens.next()
+    }
+
+    fn optional<T: FromStr>(
+        &mut self,
+        what: &'static str,
+    ) -> Result<Option<(usize, T)>, DimacsError> {
+        match self.tokens.next() {
+            Some((column, token)) => {
+                token
+                    .parse::<T>()
+                    .map(|val| Some((column, val)))
+                    .map_err(|_| {
+                        self.error(
+                            column,
+                            DimacsErrorKind::InvalidField(what, token.to_string()),
+                        )
+                    })
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, what: &'static str) -> Result<(usize, T), DimacsError> {
+        self.optional(what)?
+            .ok_or_else(|| self.error(self.end_column, DimacsErrorKind::MissingField(what)))
+    }
+
+    fn finish(mut self) -> Result<(), DimacsError> {
+        match self.tokens.next() {
+            Some((column, token)) => {
+                Err(self.error(column, DimacsErrorKind::UnexpectedField(token.to_string())))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+//A count declared in a `p` header, remembered with its position so that a body
+//disagreeing with it can be reported against the header rather than the end of the file
+#[derive(Clone, Copy)]
+struct Declared {
+    line: usize,
+    column: usize,
+    count: usize,
+}
+
+impl Declared {
+    fn read(fields: &mut Fields, what: &'static str) -> Result<Self, DimacsError> {
+        let (column, count) = fields.required::<usize>(what)?;
+        Ok(Self {
+            line: fields.line,
+            column,
+            count,
+        })
+    }
+
+    fn check(&self, path: &str, what: &'static str, found: usize) -> Result<(), DimacsError> {
+        if found == self.count {
+            return Ok(());
+        }
+        Err(DimacsError {
+            path: path.to_string(),
+            line: Some(self.line),
+            column: Some(self.column),
+            kind: DimacsErrorKind::CountMismatch {
+                what,
+                declared: self.count,
+                found,
+            },
+        })
+    }
+}
+
+//Streams the lines of a file, decompressing it on the way when it is compressed
+fn read_lines(
+    path: &str,
+) -> Result<impl Iterator<Item = Result<String, DimacsError>> + '_, DimacsError> {
+    let io_error = move |e| DimacsError::file(path, DimacsErrorKind::Io(e));
+    Ok(open_input(path)
+        .map_err(io_error)?
+        .lines()
+        .map(move |line| line.map_err(io_error)))
+}
+
+fn check_addressable<Ix: TryFrom<u32>>(
+    fields: &Fields,
+    vertices: &Declared,
+) -> Result<(), DimacsError> {
+    match vertices.count.checked_sub(1).map(u32::try_from) {
+        Some(Ok(last)) if Ix::try_from(last).is_ok() => Ok(()),
+        None => Ok(()),
+        _ => Err(fields.error(
+            vertices.column,
+            DimacsErrorKind::TooManyVertices(vertices.count),
+        )),
+    }
+}
+
+/// Reads a graph in the custom DIMACS dialect described in the README
+///
+/// The file holds a `p <vertices> <edges>` header, one `v <label>` line per vertex and one
+/// `e <u> <v> <weight>` line per edge, where `u` and `v` are 0-based positions in the vertex
+/// list and the weight may be fractional. Blank lines and `c` comment lines are ignored.
+/// Anything else, including header counts that disagree with the body, is an error. An
+/// optional `.co` file supplies positions as for `read_from_dimacs_gr`, with `v k x y`
+/// giving the position of the vertex at index `k - 1`.
+pub fn read_from_dimacs<N, E, Ix>(
+    filepath: &str,
+    co_path: Option<&str>,
+    co_options: &DimacsCoOptions,
+) -> Result<GraphRepresentation<N, E, Ix>, DimacsError>
+where
+    Ix: FromStr + Eq + Hash + TryFrom<u32>,
+    Ix::Err: Debug,
+    <Ix as TryFrom<u32>>::Error: Debug,
+    N: FromStr,
+    E: FromStr,
+{
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    //Declared vertex and edge counts
+    let mut header: Option<(Declared, Declared)> = None;
+    let mut node_count: u32 = 0;
+
+    for (i, line) in read_lines(filepath)?.enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(filepath, i + 1, &line);
+        let (column, line_type) = match fields.next_token() {
+            Some(token) => token,
+            None => continue,
+        };
+
+        match (line_type, &header) {
+            ("c", _) => continue,
+            ("p", None) => {
+                let vertices = Declared::read(&mut fields, "vertex count")?;
+                let edges = Declared::read(&mut fields, "edge count")?;
+                check_addressable::<Ix>(&fields, &vertices)?;
+                fields.finish()?;
+                header = Some((vertices, edges));
+            }
+            ("p", Some(_)) => return Err(fields.error(column, DimacsErrorKind::DuplicateHeader)),
+            (_, None) => return Err(fields.error(column, DimacsErrorKind::MissingHeader)),
+            ("v", Some((vertices, _))) => {
+                let (_, label) = fields.required::<N>("vertex label")?;
+                if node_count as usize >= vertices.count {
+                    return Err(fields.error(
+                        column,
+                        DimacsErrorKind::CountMismatch {
+                            what: "vertices",
+                            declared: vertices.count,
+                            found: node_count as usize + 1,
+                        },
+                    ));
+                }
+                let ind = Ix::try_from(node_count).unwrap();
+                fields.finish()?;
+
+                node_map.insert(ind, label);
+                node_count += 1;
+            }
+            ("e", Some((vertices, _))) => {
+                let mut endpoint = |what| -> Result<Ix, DimacsError> {
+                    let (column, vertex) = fields.required::<u64>(what)?;
+                    if vertex >= vertices.count as u64 {
+                        return Err(fields.error(
+                            column,
+                            DimacsErrorKind::VertexOutOfRange {
+                                vertex,
+                                count: vertices.count,
+                            },
+                        ));
+                    }
+                    Ok(Ix::try_from(vertex as u32).unwrap())
+                };
+                let u = endpoint("edge start vertex")?;
+                let v = endpoint("edge end vertex")?;
+                let (_, w) = fields.required::<E>("edge weight")?;
+                fields.finish()?;
+
+                edge_list.push((u, v, w));
+            }
+            (other, Some(_)) => {
+                return Err(fields.error(column, DimacsErrorKind::UnexpectedLine(other.to_string())))
+            }
+        }
+    }
+
+    let (vertices, edges) =
+        header.ok_or_else(|| DimacsError::file(filepath, DimacsErrorKind::MissingHeader))?;
+    vertices.check(filepath, "vertices", node_count as usize)?;
+    edges.check(filepath, "edges", edge_list.len())?;
+
+    let coordinates = match co_path {
+        Some(co_path) => read_co_positions(co_path, node_count, co_options)?,
+        None => HashMap::new(),
+    };
+    Ok(GraphRepresentation::new(node_map, edge_list).with_coordinates(coordinates))
+}
+
+fn read_coordinates(
+    filepath: &str,
+    node_count: u32,
+) -> Result<HashMap<u32, (f64, f64)>, DimacsError> {
+    let mut positions = HashMap::new();
+
+    for (i, line) in read_lines(filepath)?.enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(filepath, i + 1, &line);
+        match fields.next_token() {
+            None | Some((_, "c")) | Some((_, "p")) => continue,
+            Some((_, "v")) => {
+                let (column, id) = fields.required::<u32>("vertex id")?;
+                if id == 0 || id > node_count {
+                    return Err(fields.error(
+                        column,
+                        DimacsErrorKind::VertexOutOfRange {
+                            vertex: id as u64,
+                            count: node_count as usize,
+                        },
+                    ));
+                }
+                let (_, x) = fields.required::<f64>("x coordinate")?;
+                let (_, y) = fields.required::<f64>("y coordinate")?;
+                fields.finish()?;
+                positions.insert(id, (x, y));
+            }
+            Some((column, other)) => {
+                return Err(fields.error(column, DimacsErrorKind::UnexpectedLine(other.to_string())))
+            }
+        }
+    }
+
+    Ok(positions)
+}
+
+//Positions from a `.co` file, keyed by the index of the 1-based vertex id each belongs to
+fn read_co_positions<Ix>(
+    co_path: &str,
+    node_count: u32,
+    co_options: &DimacsCoOptions,
+) -> Result<HashMap<Ix, Coordinate>, DimacsError>
+where
+    Ix: Eq + Hash + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let scale = co_options.scale;
+    Ok(read_coordinates(co_path, node_count)?
+        .into_iter()
+        .map(|(id, (x, y))| {
+            (
+                Ix::try_from(id - 1).unwrap(),
+                Coordinate::new(x / scale, y / scale),
+            )
+        })
+        .collect())
+}
+
+/// Reads a graph in the 9th DIMACS Implementation Challenge shortest-path format
+///
+/// The `.gr` file supplies the `p sp n m` header and `a u v w` arcs, with 1-based vertex
+/// ids and integer or fractional weights; vertex `k` is given index `k - 1` and is labelled
+/// by its id. Because the challenge lists each road in both directions, a pair of opposing
+/// arcs with equal weights becomes a single undirected edge. An optional `.co` file supplies
+/// `v id x y` positions, in the units `co_options` describes.
+pub fn read_from_dimacs_gr<N, E, Ix>(
+    gr_path: &str,
+    co_path: Option<&str>,
+    co_options: &DimacsCoOptions,
+) -> Result<GraphRepresentation<N, E, Ix>, DimacsError>
+where
+    N: FromStr,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    //Declared vertex and arc counts
+    let mut header: Option<(Declared, Declared)> = None;
+    let mut arcs_read = 0;
+    //Counts of arcs seen in each direction, keyed by the unordered vertex pair and weight
+    let mut arc_counts = HashMap::<(u32, u32, u64), (u32, u32)>::new();
+    let mut arc_order = Vec::<(u32, u32, u64)>::new();
+
+    for (i, line) in read_lines(gr_path)?.enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(gr_path, i + 1, &line);
+        let (column, line_type) = match fields.next_token() {
+            Some(token) => token,
+            None => continue,
+        };
+
+        match (line_type, header) {
+            ("c", _) => continue,
+            ("p", None) => {
+                match fields.next_token() {
+                    Some((_, "sp")) => {}
+                    Some((column, token)) => {
+                        return Err(fields.error(
+                            column,
+                            DimacsErrorKind::InvalidField("problem type", token.to_string()),
+                        ))
+                    }
+                    None => {
+                        return Err(fields.error(
+                            fields.end_column,
+                            DimacsErrorKind::MissingField("problem type"),
+                        ))
+                    }
+                }
+                let vertices = Declared::read(&mut fields, "vertex count")?;
+                let arcs = Declared::read(&mut fields, "arc count")?;
+                check_addressable::<Ix>(&fields, &vertices)?;
+                fields.finish()?;
+                header = Some((vertices, arcs));
+            }
+            ("p", Some(_)) => return Err(fields.error(column, DimacsErrorKind::DuplicateHeader)),
+            (_, None) => return Err(fields.error(column, DimacsErrorKind::MissingHeader)),
+            ("a", Some((vertices, _))) => {
+                let mut endpoint = |what| -> Result<u32, DimacsError> {
+                    let (column, vertex) = fields.required::<u32>(what)?;
+                    if vertex == 0 || vertex as usize > vertices.count {
+                        return Err(fields.error(
+                            column,
+                            DimacsErrorKind::VertexOutOfRange {
+                                vertex: vertex as u64,
+                                count: vertices.count,
+                            },
+                        ));
+                    }
+                    Ok(vertex)
+                };
+                let u = endpoint("arc tail")?;
+                let v = endpoint("arc head")?;
+                let (_, w) = fields.required::<f64>("arc weight")?;
+                fields.finish()?;
+                arcs_read += 1;
+
+                let key = (u.min(v), u.max(v), w.to_bits());
+                let counts = arc_counts.entry(key).or_insert_with(|| {
+                    arc_order.push(key);
+                    (0, 0)
+                });
+                if u <= v {
+                    counts.0 += 1;
+                } else {
+                    counts.1 += 1;
+                }
+            }
+            (other, Some(_)) => {
+                return Err(fields.error(column, DimacsErrorKind::UnexpectedLine(other.to_string())))
+            }
+        }
+    }
+
+    let (vertices, arcs) =
+        header.ok_or_else(|| DimacsError::file(gr_path, DimacsErrorKind::MissingHeader))?;
+    arcs.check(gr_path, "arcs", arcs_read)?;
+    //check_addressable has already ensured the count fits
+    let node_count = vertices.count as u32;
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    for id in 1..=node_count {
+        let label = id.to_string().parse::<N>().map_err(|_| {
+            DimacsError::file(
+                gr_path,
+                DimacsErrorKind::InvalidField("vertex label", id.to_string()),
+            )
+        })?;
+        node_map.insert(Ix::try_from(id - 1).unwrap(), label);
+    }
+
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    arc_order.into_iter().for_each(|key| {
+        let (forward, backward) = arc_counts[&key];
+        let (u, v, w) = (
+            Ix::try_from(key.0 - 1).unwrap(),
+            Ix::try_from(key.1 - 1).unwrap(),
+            f64::from_bits(key.2),
+        );
+        for _ in 0..forward.max(backward) {
+            edge_list.push((u, v, E::from(w)));
+        }
+    });
+
+    let coordinates = match co_path {
+        Some(co_path) => read_co_positions(co_path, node_count, co_options)?,
+        None => HashMap::new(),
+    };
+    Ok(GraphRepresentation::new(node_map, edge_list).with_coordinates(coordinates))
+}
+
+/// Writes a graph in the 9th DIMACS Implementation Challenge shortest-path format
+///
+/// Vertices are numbered from 1 in index order and every edge is written as a pair of
+/// opposing arcs. When `co_path` is given and the graph has coordinates, they are written
+/// there as integers in the units `co_options` describes.
+pub fn write_to_dimacs_gr<N, E, Ix>(
+    gr: &GraphRepresentation<N, E, Ix>,
+    gr_path: &str,
+    co_path: Option<&str>,
+    co_options: &DimacsCoOptions,
+) -> Result<(), Error>
+where
+    E: Display,
+    Ix: Ord + Hash + Copy + Debug,
+{
+    let mut indices: Vec<Ix> = gr.node_map.keys().copied().collect();
+    indices.sort();
+    let ids: HashMap<Ix, usize> = indices
+        .iter()
+        .enumerate()
+        .map(|(i, ind)| (*ind, i + 1))
+        .collect();
+    let id_of = |ind: &Ix| {
+        ids.get(ind)
+            .copied()
+            .ok_or_else(|| anyhow!("Edge refers to unknown node index {:?}", ind))
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "c Written by speedicycle");
+    let _ = writeln!(out, "p sp {} {}", indices.len(), gr.edge_list.len() * 2);
+    for (u, v, w) in &gr.edge_list {
+        let (u, v) = (id_of(u)?, id_of(v)?);
+        let _ = writeln!(out, "a {} {} {}", u, v, w);
+        let _ = writeln!(out, "a {} {} {}", v, u, w);
+    }
+    fs::write(gr_path, out).with_context(|| format!("Writing {}", gr_path))?;
+
+    if let Some(co_path) = co_path {
+        let mut out = String::new();
+        let _ = writeln!(out, "c Written by speedicycle");
+        let _ = writeln!(out, "p aux sp co {}", gr.coordinates.len());
+        for ind in &indices {
+            if let Some(coord) = gr.coordinates.get(ind) {
+                let _ = writeln!(
+                    out,
+                    "v {} {} {}",
+                    ids[ind],
+                    (coord.lon * co_options.scale).round() as i64,
+                    (coord.lat * co_options.scale).round() as i64
+                );
+            }
+        }
+        fs::write(co_path, out).with_context(|| format!("Writing {}", co_path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use tempfile::NamedTempFile;
+
+    fn write_file(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn path(file: &NamedTempFile) -> &str {
+        file.path().to_str().unwrap()
+    }
+
+    #[test]
+    fn reads_fractional_edge_weights() {
+        let file = write_file("p 3 2\nv 10\nv 11\nv 12\ne 0 1 2.5\ne 1 2 4\n");
+        let gr = read_from_dimacs::<u32, f64, u32>(path(&file), None, &DimacsCoOptions::default())
+            .unwrap();
+
+        assert_eq!(gr.node_map[&2], 12);
+        assert_eq!(gr.edge_list, vec![(0, 1, 2.5), (1, 2, 4.0)]);
+    }
+
+    #[test]
+    fn reads_positions_from_a_co_file() {
+        let file = write_file("p 2 1\nv 10\nv 11\ne 0 1 3\n");
+        let co_file = write_file("p aux sp co 1\nv 2 -73985700 40748400\n");
+        let gr = read_from_dimacs::<u32, f64, u32>(
+            path(&file),
+            Some(path(&co_file)),
+            &DimacsCoOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(gr.coordinates.len(), 1);
+        assert!((gr.coordinates[&1].lon + 73.9857).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_positions_on_vertex_lines() {
+        let file = write_file("p 1 0\nv 10 -73.9857 40.7484\n");
+        let err = read_from_dimacs::<u32, f64, u32>(path(&file), None, &DimacsCoOptions::default())
+            .unwrap_err();
+
+        assert_eq!((err.line, err.column), (Some(2), Some(6)));
+        assert!(matches!(err.kind, DimacsErrorKind::UnexpectedField(_)));
+    }
+
+    #[test]
+    fn reports_the_line_and_column_at_fault() {
+        let cases: [(&str, usize, usize); 6] = [
+            ("v 10\n", 1, 1),
+            ("c comment\n\np 2 1\nv\n", 4, 2),
+            ("p 2 1\nv 10\nv 11\ne 0  x 1\n", 4, 6),
+            ("p 2 1\nv 10\nv 11\ne 0 2 1\n", 4, 5),
+            ("p 2 1\nv 10\nv 11\nx 0 1\n", 4, 1),
+            ("p 2 2\nv 10\nv 11\ne 0 1 1\n", 1, 5),
+        ];
+        for (contents, line, column) in cases {
+            let file = write_file(contents);
+            let err =
+                read_from_dimacs::<u32, f64, u32>(path(&file), None, &DimacsCoOptions::default())
+                    .unwrap_err();
+            assert_eq!(
+                (err.line, err.column),
+                (Some(line), Some(column)),
+                "{:?}: {}",
+                contents,
+                err
+            );
+            assert!(err
+                .to_string()
+                .starts_with(&format!("{}:{}:{}: ", path(&file), line, column)));
+        }
+    }
+
+    #[test]
+    fn names_what_is_wrong() {
+        let err_for = |contents: &str| {
+            let file = write_file(contents);
+            read_from_dimacs::<u32, f64, u32>(path(&file), None, &DimacsCoOptions::default())
+                .unwrap_err()
+                .kind
+        };
+
+        assert!(matches!(err_for("v 10\n"), DimacsErrorKind::MissingHeader));
+        assert!(matches!(err_for(""), DimacsErrorKind::MissingHeader));
+        assert!(matches!(
+            err_for("p 1 0\np 1 0\n"),
+            DimacsErrorKind::DuplicateHeader
+        ));
+        assert!(matches!(
+            err_for("p 2 1\nv\n"),
+            DimacsErrorKind::MissingField("vertex label")
+        ));
+        assert!(matches!(
+            err_for("p 2 1\nv 10\nv 11\ne 0 x 1\n"),
+            DimacsErrorKind::InvalidField("edge end vertex", _)
+        ));
+        assert!(matches!(
+            err_for("p 2 1\nv 10\nv 11\ne 0 2 1\n"),
+            DimacsErrorKind::VertexOutOfRange {
+                vertex: 2,
+                count: 2
+            }
+        ));
+        assert!(matches!(
+            err_for("p 2 1\nv 10\nv 11\nv 12\n"),
+            DimacsErrorKind::CountMismatch {
+                what: "vertices",
+                declared: 2,
+                found: 3
+            }
+        ));
+        assert!(matches!(
+            err_for("p 2 2\nv 10\nv 11\ne 0 1 1\n"),
+            DimacsErrorKind::CountMismatch {
+                what: "edges",
+                declared: 2,
+                found: 1
+            }
+        ));
+
+        let file = write_file("p 300 0\n");
+        let err = read_from_dimacs::<u32, f64, u8>(path(&file), None, &DimacsCoOptions::default())
+            .unwrap_err();
+        assert!(matches!(err.kind, DimacsErrorKind::TooManyVertices(300)));
+    }
+
+    #[test]
+    fn scales_co_positions_by_the_given_option() {
+        let gr_file = write_file("p sp 2 2\na 1 2 1.5\na 2 1 1.5\n");
+        let co_file = write_file("p aux sp co 2\nv 1 -73985700 40748400\nv 2 -7398 4075\n");
+
+        let gr = read_from_dimacs_gr::<u32, f64, u32>(
+            path(&gr_file),
+            Some(path(&co_file)),
+            &DimacsCoOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(gr.edge_list, vec![(0, 1, 1.5)]);
+        assert!((gr.coordinates[&0].lon + 73.9857).abs() < 1e-9);
+        assert!((gr.coordinates[&1].lat - 0.004075).abs() < 1e-9);
+
+        let gr = read_from_dimacs_gr::<u32, f64, u32>(
+            path(&gr_file),
+            Some(path(&co_file)),
+            &DimacsCoOptions { scale: 100.0 },
+        )
+        .unwrap();
+        assert!((gr.coordinates[&1].lon + 73.98).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gr_round_trips_through_the_writer() {
+        let gr_file = write_file("p sp 3 4\na 1 2 1.5\na 2 1 1.5\na 2 3 7\na 3 2 7\n");
+        let co_file = write_file("p aux sp co 3\nv 1 1000 2000\nv 2 3000 4000\nv 3 5 6\n");
+        let options = DimacsCoOptions { scale: 1000.0 };
+        let gr =
+            read_from_dimacs_gr::<u32, f64, u32>(path(&gr_file), Some(path(&co_file)), &options)
+                .unwrap();
+
+        let out_gr = NamedTempFile::new().unwrap();
+        let out_co = NamedTempFile::new().unwrap();
+        write_to_dimacs_gr(&gr, path(&out_gr), Some(path(&out_co)), &options).unwrap();
+        let reread =
+            read_from_dimacs_gr::<u32, f64, u32>(path(&out_gr), Some(path(&out_co)), &options)
+                .unwrap();
+
+        assert_eq!(reread.edge_list, gr.edge_list);
+        assert_eq!(reread.coordinates, gr.coordinates);
+    }
+}
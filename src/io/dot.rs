@@ -0,0 +1,174 @@
+use crate::graph::euler::WalkedEdge;
+use crate::graph::input_edge;
+
+use anyhow::Error;
+use petgraph::stable_graph::{IndexType, NodeIndex, StableDiGraph};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Write};
+use std::fs;
+
+//Colours given to the overlaid edge sets, in order
+const OVERLAY_COLOURS: [&str; 4] = ["red", "blue", "darkgreen", "darkorange"];
+
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Labels every edge with its weight
+    pub edge_weights: bool,
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders a graph built by `make_graph` (typically the trimmed graph of a `BandhariGraph`)
+/// as an undirected Graphviz DOT document
+///
+/// Each `(name, edges)` overlay, such as the edges walked by a circuit, is drawn in its own
+/// colour, with a legend in the graph label; edges in more than one overlay are drawn in each
+/// of their colours. Overlays name input edges, so of several edges joining the same nodes
+/// only those walked are coloured. The source node
+/// is drawn as a double circle labelled `source`.
+pub fn graph_to_dot<N, E, Ix>(
+    graph: &StableDiGraph<N, E, Ix>,
+    overlays: &[(&str, &[WalkedEdge<usize>])],
+    source: NodeIndex<Ix>,
+    options: &DotOptions,
+) -> String
+where
+    N: Display,
+    E: Into<f64> + Copy,
+    Ix: IndexType,
+{
+    let overlay_ids: Vec<HashSet<usize>> = overlays
+        .iter()
+        .map(|(_, edges)| edges.iter().map(|edge| edge.id).collect())
+        .collect();
+    let colour_of = |i: usize| OVERLAY_COLOURS[i % OVERLAY_COLOURS.len()];
+
+    let mut out = String::new();
+    out.push_str("graph speedicycle {\n");
+    let legend = overlays
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| format!("{}: {}", escape_dot(name), colour_of(i)))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let _ = writeln!(out, "  label=\"{}\";", legend);
+    out.push_str("  node [shape=circle, fontsize=10];\n  edge [color=gray];\n");
+
+    graph.node_indices().for_each(|ind| {
+        let label = escape_dot(&graph[ind].to_string());
+        if ind == source {
+            let _ = writeln!(
+                out,
+                "  n{} [label=\"{}\", xlabel=\"source\", shape=doublecircle, style=filled, fillcolor=yellow];",
+                ind.index(),
+                label
+            );
+        } else {
+            let _ = writeln!(out, "  n{} [label=\"{}\"];", ind.index(), label);
+        }
+    });
+
+    //make_graph stores each undirected edge as a pair of opposing arcs, so only the arc
+    //running from the lower to the higher index is drawn (and every other self-loop arc)
+    let mut loop_seen = HashMap::<NodeIndex<Ix>, bool>::new();
+    graph.edge_indices().for_each(|e| {
+        let (u, v) = graph.edge_endpoints(e).unwrap();
+        if u > v {
+            return;
+        }
+        if u == v {
+            let seen = loop_seen.entry(u).or_default();
+            *seen = !*seen;
+            if !*seen {
+                return;
+            }
+        }
+
+        let mut attributes = Vec::new();
+        let id = input_edge(e).id;
+        let colours: Vec<&str> = overlay_ids
+            .iter()
+            .enumerate()
+            .filter(|(_, ids)| ids.contains(&id))
+            .map(|(i, _)| colour_of(i))
+            .collect();
+        if !colours.is_empty() {
+            attributes.push(format!("color=\"{}\"", colours.join(":")));
+            attributes.push("penwidth=2".to_string());
+        }
+        if options.edge_weights {
+            let w: f64 = graph[e].into();
+            attributes.push(format!("label=\"{}\"", w));
+        }
+
+        let _ = write!(out, "  n{} -- n{}", u.index(), v.index());
+        if !attributes.is_empty() {
+            let _ = write!(out, " [{}]", attributes.join(", "));
+        }
+        out.push_str(";\n");
+    });
+
+    out.push_str("}\n");
+    out
+}
+
+pub fn write_graph_to_dot<N, E, Ix>(
+    path: &str,
+    graph: &StableDiGraph<N, E, Ix>,
+    overlays: &[(&str, &[WalkedEdge<usize>])],
+    source: NodeIndex<Ix>,
+    options: &DotOptions,
+) -> Result<(), Error>
+where
+    N: Display,
+    E: Into<f64> + Copy,
+    Ix: IndexType,
+{
+    fs::write(path, graph_to_dot(graph, overlays, source, options))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Two parallel edges join nodes 10 and 11, as arcs 0 and 1 and arcs 2 and 3 do after
+    //`make_graph`
+    fn parallel_edges() -> StableDiGraph<u32, f64, u32> {
+        let mut graph = StableDiGraph::default();
+        let a = graph.add_node(10);
+        let b = graph.add_node(11);
+        for w in [1.0, 2.5] {
+            graph.add_edge(a, b, w);
+            graph.add_edge(b, a, w);
+        }
+        graph
+    }
+
+    #[test]
+    fn colours_only_the_parallel_edge_walked() {
+        let graph = parallel_edges();
+        let walked = [WalkedEdge {
+            id: 1,
+            forward: false,
+        }];
+        let dot = graph_to_dot(
+            &graph,
+            &[("upper", &walked)],
+            NodeIndex::new(0),
+            &DotOptions { edge_weights: true },
+        );
+        let edges: Vec<&str> = dot.lines().filter(|line| line.contains(" -- ")).collect();
+
+        assert!(dot.contains("label=\"upper: red\";"));
+        assert_eq!(
+            edges,
+            vec![
+                "  n0 -- n1 [label=\"1\"];",
+                "  n0 -- n1 [color=\"red\", penwidth=2, label=\"2.5\"];",
+            ]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use super::geometry::{circuit_positions, Coordinate, EdgeGeometries};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::{anyhow, Error};
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//FIT timestamps count seconds from 1989-12-31T00:00:00Z rather than the Unix epoch
+const FIT_EPOCH_OFFSET: u64 = 631_065_600;
+const FIT_PROTOCOL_VERSION: u8 = 0x20;
+const FIT_PROFILE_VERSION: u16 = 2132;
+const COURSE_NAME_SIZE: u8 = 16;
+
+//Global message numbers
+const MESG_FILE_ID: u16 = 0;
+const MESG_LAP: u16 = 19;
+const MESG_RECORD: u16 = 20;
+const MESG_COURSE: u16 = 31;
+const MESG_COURSE_POINT: u16 = 32;
+
+//Base types
+const ENUM: u8 = 0x00;
+const STRING: u8 = 0x07;
+const UINT16: u8 = 0x84;
+const SINT32: u8 = 0x85;
+const UINT32: u8 = 0x86;
+const UINT32Z: u8 = 0x8C;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+fn fit_crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, byte| {
+        let mut tmp = CRC_TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ CRC_TABLE[(byte & 0xF) as usize];
+        tmp = CRC_TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc ^ tmp ^ CRC_TABLE[((byte >> 4) & 0xF) as usize]
+    })
+}
+
+fn to_semicircles(degrees: f64) -> i32 {
+    (degrees * (2f64.powi(31) / 180.0)).round() as i32
+}
+
+/// Sport recorded in the course message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitSport {
+    Generic = 0,
+    Running = 1,
+    Cycling = 2,
+    Walking = 11,
+}
+
+#[derive(Debug, Clone)]
+pub struct FitOptions {
+    /// Course name shown on the device; truncated to 15 bytes
+    pub name: String,
+    pub sport: FitSport,
+    /// Assumed speed in meters per second, used to give each record a timestamp
+    pub speed: f64,
+    /// Time the course starts; defaults to the time of encoding
+    pub start_time: Option<SystemTime>,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        Self {
+            name: "speedicycle".to_string(),
+            sport: FitSport::Running,
+            speed: 3.0,
+            start_time: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FitWriter {
+    data: Vec<u8>,
+}
+
+impl FitWriter {
+    //Fields are given as (field definition number, size, base type)
+    fn define(&mut self, local_type: u8, global_type: u16, fields: &[(u8, u8, u8)]) {
+        self.data.push(0x40 | local_type);
+        self.data.push(0);
+        //Little-endian architecture
+        self.data.push(0);
+        self.data.extend_from_slice(&global_type.to_le_bytes());
+        self.data.push(fields.len() as u8);
+        fields
+            .iter()
+            .for_each(|(num, size, base)| self.data.extend_from_slice(&[*num, *size, *base]));
+    }
+
+    fn begin(&mut self, local_type: u8) {
+        self.data.push(local_type);
+    }
+
+    fn u8(&mut self, val: u8) {
+        self.data.push(val);
+    }
+
+    fn u16(&mut self, val: u16) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn u32(&mut self, val: u32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn i32(&mut self, val: i32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn string(&mut self, val: &str, size: u8) {
+        let mut bytes: Vec<u8> = val.bytes().take(size as usize - 1).collect();
+        bytes.resize(size as usize, 0);
+        self.data.extend_from_slice(&bytes);
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.data.len() + 16);
+        out.push(14);
+        out.push(FIT_PROTOCOL_VERSION);
+        out.extend_from_slice(&FIT_PROFILE_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(b".FIT");
+        let header_crc = fit_crc(&out);
+        out.extend_from_slice(&header_crc.to_le_bytes());
+
+        out.extend_from_slice(&self.data);
+        let crc = fit_crc(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+}
+
+/// Encodes a FIT course from positions paired with the cumulative distance (in meters)
+/// at which each is reached
+pub fn encode_fit_course(
+    track: &[(Coordinate, f64)],
+    options: &FitOptions,
+) -> Result<Vec<u8>, Error> {
+    let (first, last) = match (track.first(), track.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(anyhow!("Cannot encode an empty course")),
+    };
+    if options.speed <= 0.0 {
+        return Err(anyhow!("Course speed must be positive"));
+    }
+
+    let start_time = options
+        .start_time
+        .unwrap_or_else(SystemTime::now)
+        .duration_since(UNIX_EPOCH)?
+        .as_secs()
+        .saturating_sub(FIT_EPOCH_OFFSET) as u32;
+    let time_at = |distance: f64| start_time + (distance / options.speed).round() as u32;
+    let total_distance = last.1;
+    let total_time = total_distance / options.speed;
+
+    let mut w = FitWriter::default();
+
+    w.define(
+        0,
+        MESG_FILE_ID,
+        &[
+            (0, 1, ENUM),
+            (1, 2, UINT16),
+            (2, 2, UINT16),
+            (3, 4, UINT32Z),
+            (4, 4, UINT32),
+        ],
+    );
+    w.begin(0);
+    //Course file, development manufacturer
+    w.u8(6);
+    w.u16(255);
+    w.u16(0);
+    w.u32(1);
+    w.u32(start_time);
+
+    w.define(
+        1,
+        MESG_COURSE,
+        &[(4, 1, ENUM), (5, COURSE_NAME_SIZE, STRING)],
+    );
+    w.begin(1);
+    w.u8(options.sport as u8);
+    w.string(&options.name, COURSE_NAME_SIZE);
+
+    w.define(
+        2,
+        MESG_LAP,
+        &[
+            (253, 4, UINT32),
+            (2, 4, UINT32),
+            (3, 4, SINT32),
+            (4, 4, SINT32),
+            (5, 4, SINT32),
+            (6, 4, SINT32),
+            (7, 4, UINT32),
+            (8, 4, UINT32),
+            (9, 4, UINT32),
+        ],
+    );
+    w.begin(2);
+    w.u32(time_at(total_distance));
+    w.u32(start_time);
+    w.i32(to_semicircles(first.0.lat));
+    w.i32(to_semicircles(first.0.lon));
+    w.i32(to_semicircles(last.0.lat));
+    w.i32(to_semicircles(last.0.lon));
+    w.u32((total_time * 1000.0).round() as u32);
+    w.u32((total_time * 1000.0).round() as u32);
+    w.u32((total_distance * 100.0).round() as u32);
+
+    w.define(
+        3,
+        MESG_RECORD,
+        &[
+            (253, 4, UINT32),
+            (0, 4, SINT32),
+            (1, 4, SINT32),
+            (2, 2, UINT16),
+            (5, 4, UINT32),
+        ],
+    );
+    track.iter().for_each(|(coord, distance)| {
+        w.begin(3);
+        w.u32(time_at(*distance));
+        w.i32(to_semicircles(coord.lat));
+        w.i32(to_semicircles(coord.lon));
+        //Altitude is stored with a scale of 5 and an offset of 500m; 0xFFFF marks it as absent
+        w.u16(
+            coord
+                .elevation
+                .map(|e| ((e + 500.0) * 5.0).round().clamp(0.0, 65534.0) as u16)
+                .unwrap_or(u16::MAX),
+        );
+        w.u32((distance * 100.0).round() as u32);
+    });
+
+    w.define(
+        4,
+        MESG_COURSE_POINT,
+        &[
+            (1, 4, UINT32),
+            (2, 4, SINT32),
+            (3, 4, SINT32),
+            (4, 4, UINT32),
+            (5, 1, ENUM),
+            (6, COURSE_NAME_SIZE, STRING),
+        ],
+    );
+    [(first, "Start"), (last, "Finish")]
+        .iter()
+        .for_each(|((coord, distance), name)| {
+            w.begin(4);
+            w.u32(time_at(*distance));
+            w.i32(to_semicircles(coord.lat));
+            w.i32(to_semicircles(coord.lon));
+            w.u32((distance * 100.0).round() as u32);
+            //Generic course point
+            w.u8(0);
+            w.string(name, COURSE_NAME_SIZE);
+        });
+
+    Ok(w.finish())
+}
+
+/// Encodes a circuit as a FIT course, measuring cumulative distance along its coordinates
+/// and following the shapes in `edge_geometries` where given
+pub fn circuit_to_fit<G>(
+    circuit: &EulerCircuit<G>,
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &FitOptions,
+) -> Result<Vec<u8>, Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    let mut distance = 0.0;
+    let mut previous: Option<Coordinate> = None;
+    let track: Vec<(Coordinate, f64)> = circuit_positions(
+        &circuit.ordered_node_weight_list,
+        &circuit.edge_list,
+        coordinates,
+        edge_geometries,
+    )?
+    .into_iter()
+    .map(|coord| {
+        if let Some(prev) = previous {
+            distance += prev.haversine_distance(&coord);
+        }
+        previous = Some(coord);
+        (coord, distance)
+    })
+    .collect();
+
+    encode_fit_course(&track, options)
+}
+
+pub fn write_circuit_to_fit<G>(
+    path: &str,
+    circuit: &EulerCircuit<G>,
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &FitOptions,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    fs::write(
+        path,
+        circuit_to_fit(circuit, coordinates, edge_geometries, options)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn course() -> Vec<u8> {
+        let track = [
+            (Coordinate::new(-73.0, 40.0).with_elevation(12.0), 0.0),
+            (Coordinate::new(-73.0, 40.001), 111.2),
+            (Coordinate::new(-73.0, 40.0), 222.4),
+        ];
+        let options = FitOptions {
+            start_time: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            ..Default::default()
+        };
+        encode_fit_course(&track, &options).unwrap()
+    }
+
+    //Counts the data messages of each global message type, checking each one fits its
+    //definition
+    fn message_counts(data: &[u8]) -> HashMap<u16, usize> {
+        let mut definitions = HashMap::<u8, (u16, usize)>::new();
+        let mut counts = HashMap::new();
+        let mut i = 0;
+        while i < data.len() {
+            let local_type = data[i] & 0x0F;
+            if data[i] & 0x40 != 0 {
+                let global_type = u16::from_le_bytes([data[i + 3], data[i + 4]]);
+                let fields = data[i + 5] as usize;
+                let size = (0..fields).map(|f| data[i + 7 + 3 * f] as usize).sum();
+                definitions.insert(local_type, (global_type, size));
+                i += 6 + 3 * fields;
+            } else {
+                let (global_type, size) = definitions[&local_type];
+                *counts.entry(global_type).or_insert(0) += 1;
+                i += 1 + size;
+            }
+        }
+        assert_eq!(i, data.len());
+        counts
+    }
+
+    #[test]
+    fn crc_matches_the_fit_sdk() {
+        //The FIT CRC is CRC-16/ARC, whose check value this is
+        assert_eq!(fit_crc(b"123456789"), 0xBB3D);
+        assert_eq!(fit_crc(b""), 0);
+    }
+
+    #[test]
+    fn header_and_file_crcs_are_valid() {
+        let fit = course();
+
+        assert_eq!(fit[0], 14);
+        assert_eq!(&fit[8..12], b".FIT");
+        let data_size = u32::from_le_bytes(fit[4..8].try_into().unwrap()) as usize;
+        assert_eq!(data_size, fit.len() - 16);
+        assert_eq!(u16::from_le_bytes([fit[12], fit[13]]), fit_crc(&fit[..12]));
+        let end = fit.len() - 2;
+        assert_eq!(
+            u16::from_le_bytes([fit[end], fit[end + 1]]),
+            fit_crc(&fit[..end])
+        );
+        //A file followed by its own CRC checks to zero, as FIT readers verify it
+        assert_eq!(fit_crc(&fit), 0);
+    }
+
+    #[test]
+    fn writes_one_record_per_position() {
+        let fit = course();
+        let counts = message_counts(&fit[14..fit.len() - 2]);
+
+        assert_eq!(counts[&MESG_FILE_ID], 1);
+        assert_eq!(counts[&MESG_COURSE], 1);
+        assert_eq!(counts[&MESG_LAP], 1);
+        assert_eq!(counts[&MESG_RECORD], 3);
+        assert_eq!(counts[&MESG_COURSE_POINT], 2);
+    }
+
+    #[test]
+    fn rejects_empty_courses() {
+        assert!(encode_fit_course(&[], &FitOptions::default()).is_err());
+    }
+}
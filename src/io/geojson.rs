@@ -0,0 +1,452 @@
+use super::compression::open_input;
+use super::geometry::{
+    circuit_positions, geodesic_length, Coordinate, CoordinateSnapper, EdgeGeometries,
+};
+use super::{Attributes, GraphRepresentation};
+use crate::graph::euler::WalkedEdge;
+use crate::RoutingResults;
+
+use anyhow::{anyhow, Context, Error};
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, Position, Value};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+
+/// Controls how a GeoJSON street network is turned into a graph
+#[derive(Debug, Clone)]
+pub struct GeoJsonOptions {
+    /// Endpoints closer than this distance (in coordinate units, i.e. degrees) share a node
+    pub snap_tolerance: f64,
+    /// Feature property holding each edge's weight; geodesic length in meters is used when `None`
+    pub weight_property: Option<String>,
+}
+
+impl Default for GeoJsonOptions {
+    fn default() -> Self {
+        Self {
+            snap_tolerance: 1e-7,
+            weight_property: None,
+        }
+    }
+}
+
+fn position_to_coordinate(position: &Position) -> Result<Coordinate, Error> {
+    match position.as_slice() {
+        [lon, lat] => Ok(Coordinate::new(*lon, *lat)),
+        [lon, lat, elevation, ..] => Ok(Coordinate::new(*lon, *lat).with_elevation(*elevation)),
+        _ => Err(anyhow!("Position {:?} has fewer than two values", position)),
+    }
+}
+
+//Each LineString (or each part of a MultiLineString) becomes a single edge between
+//its first and last positions; intermediate positions contribute to its length, and
+//are kept as the edge's geometry.
+//Features with other geometry types are skipped, as are lines whose endpoints snap
+//to the same node. Each edge keeps its feature's properties (other than the weight
+//property) as attributes.
+#[allow(clippy::type_complexity)]
+pub fn read_from_geojson<N, E, Ix>(
+    geojson_string: &str,
+    options: &GeoJsonOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<u32>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    geojson_to_graph(geojson_string.parse::<GeoJson>()?, options)
+}
+
+/// Reads a graph from a GeoJSON file, which may be gzip or zstd compressed, as
+/// `read_from_geojson` does
+///
+/// The file is parsed as it is read, without first being read into memory whole.
+#[allow(clippy::type_complexity)]
+pub fn read_from_geojson_file<N, E, Ix>(
+    path: &str,
+    options: &GeoJsonOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<u32>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let geojson = open_input(path)
+        .map_err(Error::from)
+        .and_then(|reader| Ok(GeoJson::from_reader(reader)?))
+        .with_context(|| format!("Reading {}", path))?;
+    geojson_to_graph(geojson, options)
+}
+
+#[allow(clippy::type_complexity)]
+fn geojson_to_graph<N, E, Ix>(
+    geojson: GeoJson,
+    options: &GeoJsonOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<u32>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let feature_collection = match geojson {
+        GeoJson::FeatureCollection(fc) => fc,
+        _ => return Err(anyhow!("Expected a GeoJSON FeatureCollection")),
+    };
+
+    let mut snapper = CoordinateSnapper::new(options.snap_tolerance);
+    let mut edges = Vec::<(u32, u32, f64)>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+    let mut edge_geometries = EdgeGeometries::new();
+
+    for (i, feature) in feature_collection.features.iter().enumerate() {
+        let lines: Vec<&Vec<Position>> = match feature.geometry.as_ref().map(|g| &g.value) {
+            Some(Value::LineString(line)) => vec![line],
+            Some(Value::MultiLineString(lines)) => lines.iter().collect(),
+            _ => continue,
+        };
+
+        let parts = lines
+            .into_iter()
+            .map(|line| line.iter().map(position_to_coordinate).collect())
+            .collect::<Result<Vec<Vec<Coordinate>>, Error>>()?;
+        let lengths: Vec<f64> = parts.iter().map(|part| geodesic_length(part)).collect();
+        let total_length: f64 = lengths.iter().sum();
+
+        let property_weight = match &options.weight_property {
+            Some(key) => Some(
+                feature
+                    .property(key)
+                    .and_then(|val| val.as_f64())
+                    .ok_or_else(|| anyhow!("Feature {} has no numeric `{}` property", i, key))?,
+            ),
+            None => None,
+        };
+        let mut properties = feature.properties.clone().unwrap_or_default();
+        if let Some(key) = &options.weight_property {
+            properties.remove(key);
+        }
+
+        for (part, length) in parts.iter().zip(lengths) {
+            if let (Some(first), Some(last)) = (part.first(), part.last()) {
+                let u = snapper.snap(*first);
+                let v = snapper.snap(*last);
+                if u == v {
+                    continue;
+                }
+
+                //A property weight describes the whole feature, so it is shared
+                //among the parts of a MultiLineString in proportion to their length
+                let w = match property_weight {
+                    Some(w) if parts.len() > 1 && total_length > 0.0 => w * length / total_length,
+                    Some(w) => w,
+                    None => length,
+                };
+                edge_geometries.insert(edges.len(), part.clone());
+                edges.push((u, v, w));
+                edge_attributes.push(properties.clone());
+            }
+        }
+    }
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+
+    let mut node_indices = Vec::<Ix>::with_capacity(snapper.coordinates.len());
+    for (i, coord) in (0..).zip(snapper.coordinates) {
+        let too_many = || anyhow!("The network has more nodes than the node index type can hold");
+        let ind = Ix::try_from(i).map_err(|_| too_many())?;
+        let node_id = N::try_from(i).map_err(|_| too_many())?;
+        node_map.insert(ind, node_id);
+        node_weight_to_index.insert(node_id, ind);
+        coordinates.insert(ind, coord);
+        node_indices.push(ind);
+    }
+
+    let edge_list = edges
+        .into_iter()
+        .map(|(u, v, w)| {
+            (
+                node_indices[u as usize],
+                node_indices[v as usize],
+                E::from(w),
+            )
+        })
+        .collect();
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(HashMap::new(), edge_attributes)
+            .with_edge_geometries(edge_geometries),
+        node_weight_to_index,
+    ))
+}
+
+fn circuit_to_feature<N, E, K>(
+    name: &str,
+    circuit: &[N],
+    edges: &[WalkedEdge<K>],
+    length: E,
+    target_length: E,
+    coordinates: &HashMap<N, Coordinate>,
+    edge_geometries: &EdgeGeometries<K>,
+) -> Result<Feature, Error>
+where
+    N: Eq + Hash + Debug,
+    E: Into<f64>,
+    K: Eq + Hash,
+{
+    let line = circuit_positions(circuit, edges, coordinates, edge_geometries)?
+        .into_iter()
+        .map(|c| match c.elevation {
+            Some(elevation) => vec![c.lon, c.lat, elevation],
+            None => vec![c.lon, c.lat],
+        })
+        .collect::<Vec<Position>>();
+
+    let length: f64 = length.into();
+    let target_length: f64 = target_length.into();
+
+    let mut properties = JsonObject::new();
+    properties.insert("circuit".to_string(), json!(name));
+    properties.insert("target_length".to_string(), json!(target_length));
+    properties.insert("length".to_string(), json!(length));
+    properties.insert("deviation".to_string(), json!(length - target_length));
+    properties.insert("node_count".to_string(), json!(circuit.len()));
+    properties.insert(
+        "edge_count".to_string(),
+        json!(circuit.len().saturating_sub(1)),
+    );
+
+    Ok(Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(Value::LineString(line))),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    })
+}
+
+/// Builds a FeatureCollection holding the upper and lower circuits as LineStrings, following
+/// the shapes in `edge_geometries` (keyed like the results' walked edges) where given
+pub fn circuits_to_geojson<N, E, K>(
+    results: &RoutingResults<N, E, K>,
+    coordinates: &HashMap<N, Coordinate>,
+    edge_geometries: &EdgeGeometries<K>,
+) -> Result<FeatureCollection, Error>
+where
+    N: Eq + Hash + Debug,
+    E: Into<f64> + Copy,
+    K: Eq + Hash,
+{
+    let features = vec![
+        circuit_to_feature(
+            "upper",
+            &results.upper,
+            &results.upper_edges,
+            results.upper_length,
+            results.target_length,
+            coordinates,
+            edge_geometries,
+        )?,
+        circuit_to_feature(
+            "lower",
+            &results.lower,
+            &results.lower_edges,
+            results.lower_length,
+            results.target_length,
+            coordinates,
+            edge_geometries,
+        )?,
+    ];
+
+    Ok(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
+}
+
+pub fn write_circuits_to_geojson<N, E, K>(
+    path: &str,
+    results: &RoutingResults<N, E, K>,
+    coordinates: &HashMap<N, Coordinate>,
+    edge_geometries: &EdgeGeometries<K>,
+) -> Result<(), Error>
+where
+    N: Eq + Hash + Debug,
+    E: Into<f64> + Copy,
+    K: Eq + Hash,
+{
+    let feature_collection = circuits_to_geojson(results, coordinates, edge_geometries)?;
+    fs::write(path, GeoJson::from(feature_collection).to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREETS: &str = r#"{"type": "FeatureCollection", "features": [
+        {"type": "Feature", "properties": {"name": "Main", "length": 120.0},
+         "geometry": {"type": "LineString", "coordinates": [[-73.9, 40.7], [-73.9, 40.701]]}},
+        {"type": "Feature", "properties": {"length": 80.0},
+         "geometry": {"type": "MultiLineString", "coordinates": [
+            [[-73.9, 40.701], [-73.899, 40.701]],
+            [[-73.899, 40.701], [-73.899, 40.7], [-73.9, 40.7]]]}}]}"#;
+
+    #[test]
+    fn snaps_shared_endpoints_and_shares_weights_among_parts() {
+        let options = GeoJsonOptions {
+            weight_property: Some("length".to_string()),
+            ..Default::default()
+        };
+        let (gr, _) = read_from_geojson::<u32, f64, u32>(STREETS, &options).unwrap();
+
+        assert_eq!(gr.node_map.len(), 3);
+        assert_eq!(gr.edge_list.len(), 3);
+        assert_eq!(gr.edge_list[0].2, 120.0);
+        //The second part covers two sides of the square to the first part's one
+        assert!((gr.edge_list[1].2 + gr.edge_list[2].2 - 80.0).abs() < 1e-9);
+        assert!(gr.edge_list[2].2 > 2.0 * gr.edge_list[1].2);
+        assert_eq!(gr.edge_attributes[0]["name"], "Main");
+        assert_eq!(gr.edge_geometries[&2].len(), 3);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn streams_compressed_files() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut encoder = GzEncoder::new(NamedTempFile::new().unwrap(), Compression::default());
+        encoder.write_all(STREETS.as_bytes()).unwrap();
+        let file = encoder.finish().unwrap();
+
+        let (gr, _) = read_from_geojson_file::<u32, f64, u32>(
+            file.path().to_str().unwrap(),
+            &GeoJsonOptions::default(),
+        )
+        .unwrap();
+        let (from_string, _) =
+            read_from_geojson::<u32, f64, u32>(STREETS, &GeoJsonOptions::default()).unwrap();
+        assert_eq!(gr.edge_list, from_string.edge_list);
+        assert!((gr.edge_list[0].2 - 111.2).abs() < 0.5);
+    }
+
+    #[test]
+    fn rejects_more_nodes_than_the_label_type_holds() {
+        //150 disjoint streets make 300 nodes, more than a u8 label can number
+        let features: Vec<String> = (0..150)
+            .map(|i| {
+                format!(
+                    r#"{{"type": "Feature", "properties": {{}}, "geometry": {{"type": "LineString", "coordinates": [[{0}, 0.0], [{0}, 0.001]]}}}}"#,
+                    i as f64 * 0.01
+                )
+            })
+            .collect();
+        let collection = format!(
+            r#"{{"type": "FeatureCollection", "features": [{}]}}"#,
+            features.join(",")
+        );
+
+        let result = read_from_geojson::<u8, f64, u32>(&collection, &GeoJsonOptions::default());
+        assert!(result.unwrap_err().to_string().contains("more nodes"));
+    }
+
+    fn walked(id: usize, forward: bool) -> WalkedEdge<usize> {
+        WalkedEdge { id, forward }
+    }
+
+    #[test]
+    fn writes_both_circuits_as_linestrings_with_their_lengths() {
+        let results = RoutingResults {
+            upper: vec![1, 2, 3, 1],
+            lower: vec![1, 2, 1],
+            upper_edges: vec![walked(0, true), walked(1, false), walked(2, true)],
+            lower_edges: vec![walked(0, true), walked(0, false)],
+            upper_attributes: Vec::new(),
+            lower_attributes: Vec::new(),
+            upper_length: 330.0,
+            lower_length: 230.0,
+            target_length: 300.0,
+        };
+        let coordinates = HashMap::from([
+            (1, Coordinate::new(-73.9, 40.7).with_elevation(5.0)),
+            (2, Coordinate::new(-73.9, 40.701).with_elevation(6.0)),
+            (3, Coordinate::new(-73.899, 40.701).with_elevation(7.0)),
+        ]);
+        //Edge 1 runs from node 3 to node 2, by way of a bend
+        let edge_geometries = EdgeGeometries::from([(
+            1,
+            vec![
+                coordinates[&3],
+                Coordinate::new(-73.8995, 40.7015).with_elevation(8.0),
+                coordinates[&2],
+            ],
+        )]);
+
+        let collection = circuits_to_geojson(&results, &coordinates, &edge_geometries).unwrap();
+        assert_eq!(collection.features.len(), 2);
+
+        let upper = &collection.features[0];
+        assert_eq!(
+            upper.geometry.as_ref().unwrap().value,
+            Value::LineString(vec![
+                vec![-73.9, 40.7, 5.0],
+                vec![-73.9, 40.701, 6.0],
+                vec![-73.8995, 40.7015, 8.0],
+                vec![-73.899, 40.701, 7.0],
+                vec![-73.9, 40.7, 5.0],
+            ])
+        );
+        let properties = upper.properties.as_ref().unwrap();
+        assert_eq!(properties["circuit"], "upper");
+        assert_eq!(properties["target_length"], 300.0);
+        assert_eq!(properties["length"], 330.0);
+        assert_eq!(properties["deviation"], 30.0);
+        assert_eq!(properties["node_count"], 4);
+        assert_eq!(properties["edge_count"], 3);
+
+        let lower = &collection.features[1];
+        match &lower.geometry.as_ref().unwrap().value {
+            Value::LineString(line) => assert_eq!(line.len(), 3),
+            other => panic!("expected a LineString, not {:?}", other),
+        }
+        let properties = lower.properties.as_ref().unwrap();
+        assert_eq!(properties["circuit"], "lower");
+        assert_eq!(properties["deviation"], -70.0);
+        assert_eq!(properties["node_count"], 3);
+        assert_eq!(properties["edge_count"], 2);
+    }
+
+    #[test]
+    fn refuses_circuits_through_nodes_without_coordinates() {
+        let results = RoutingResults::<u32, f64> {
+            upper: vec![1, 2, 1],
+            lower: vec![1, 2, 1],
+            upper_edges: Vec::new(),
+            lower_edges: Vec::new(),
+            upper_attributes: Vec::new(),
+            lower_attributes: Vec::new(),
+            upper_length: 2.0,
+            lower_length: 2.0,
+            target_length: 2.0,
+        };
+        let coordinates = HashMap::from([(1, Coordinate::new(-73.9, 40.7))]);
+
+        let err = circuits_to_geojson(&results, &coordinates, &EdgeGeometries::new()).unwrap_err();
+        assert_eq!(err.to_string(), "No coordinates for node 2");
+    }
+}
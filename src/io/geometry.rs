@@ -0,0 +1,160 @@
+use crate::graph::euler::WalkedEdge;
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// Mean radius of the Earth, in meters, as used for haversine distances
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// A WGS84 position, stored in GeoJSON (longitude, latitude) order, with
+/// an optional elevation in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinate {
+    pub lon: f64,
+    pub lat: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f64>,
+}
+
+impl Coordinate {
+    pub fn new(lon: f64, lat: f64) -> Self {
+        Self {
+            lon,
+            lat,
+            elevation: None,
+        }
+    }
+
+    pub fn with_elevation(mut self, elevation: f64) -> Self {
+        self.elevation = Some(elevation);
+        self
+    }
+
+    /// Great-circle distance to `other`, in meters
+    pub fn haversine_distance(&self, other: &Coordinate) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+    }
+}
+
+/// Sums the haversine distances between consecutive coordinates
+pub fn geodesic_length(coords: &[Coordinate]) -> f64 {
+    coords
+        .iter()
+        .zip(coords.iter().skip(1))
+        .map(|(a, b)| a.haversine_distance(b))
+        .sum()
+}
+
+/// The shape of each edge that has one, running from its input start node to its input end
+/// node, keyed by edge id (by default the edge's position in the input edge list)
+pub type EdgeGeometries<K = usize> = HashMap<K, Vec<Coordinate>>;
+
+/// The positions a circuit passes through, following the shape of each edge walked where
+/// one is known and running straight between the coordinates of its nodes otherwise
+///
+/// Shapes of edges walked against their stored direction are reversed, and each joint
+/// between consecutive edges appears once. Without one walked edge per step, the circuit
+/// is drawn through its node coordinates alone.
+pub fn circuit_positions<N, K>(
+    nodes: &[N],
+    edges: &[WalkedEdge<K>],
+    coordinates: &HashMap<N, Coordinate>,
+    geometries: &EdgeGeometries<K>,
+) -> Result<Vec<Coordinate>, Error>
+where
+    N: Eq + Hash + Debug,
+    K: Eq + Hash,
+{
+    let node_coordinate = |node: &N| {
+        coordinates
+            .get(node)
+            .copied()
+            .ok_or_else(|| anyhow!("No coordinates for node {:?}", node))
+    };
+    if geometries.is_empty() || edges.is_empty() || edges.len() + 1 != nodes.len() {
+        return nodes.iter().map(node_coordinate).collect();
+    }
+
+    let mut positions = Vec::<Coordinate>::new();
+    for (pair, edge) in nodes.windows(2).zip(edges) {
+        let shape: Vec<Coordinate> = match geometries.get(&edge.id) {
+            Some(shape) if edge.forward => shape.clone(),
+            Some(shape) => shape.iter().rev().copied().collect(),
+            None => vec![node_coordinate(&pair[0])?, node_coordinate(&pair[1])?],
+        };
+        //Each edge starts where the one before it ended, so only the first keeps its start
+        let joint = usize::from(!positions.is_empty());
+        positions.extend(shape.into_iter().skip(joint));
+    }
+    Ok(positions)
+}
+
+//Assigns a node number to each distinct coordinate, treating any two coordinates
+//within `tolerance` (in coordinate units) of one another as the same node. Coordinates
+//are bucketed into a grid of `tolerance`-sized cells, so only the neighbouring
+//cells need to be searched for a match.
+pub(crate) struct CoordinateSnapper {
+    tolerance: f64,
+    cells: HashMap<(i64, i64), Vec<u32>>,
+    pub coordinates: Vec<Coordinate>,
+}
+
+impl CoordinateSnapper {
+    pub fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            cells: HashMap::new(),
+            coordinates: Vec::new(),
+        }
+    }
+
+    fn cell(&self, coord: &Coordinate) -> (i64, i64) {
+        if self.tolerance > 0.0 {
+            (
+                (coord.lon / self.tolerance).floor() as i64,
+                (coord.lat / self.tolerance).floor() as i64,
+            )
+        } else {
+            (coord.lon.to_bits() as i64, coord.lat.to_bits() as i64)
+        }
+    }
+
+    /// Returns the node number for `coord`, creating a new node if no existing one is close enough
+    pub fn snap(&mut self, coord: Coordinate) -> u32 {
+        let (cx, cy) = self.cell(&coord);
+
+        if self.tolerance > 0.0 {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if let Some(candidates) = self.cells.get(&(cx + dx, cy + dy)) {
+                        if let Some(found) = candidates.iter().find(|i| {
+                            let other = &self.coordinates[**i as usize];
+                            (other.lon - coord.lon).hypot(other.lat - coord.lat) <= self.tolerance
+                        }) {
+                            return *found;
+                        }
+                    }
+                }
+            }
+        } else if let Some(found) = self
+            .cells
+            .get(&(cx, cy))
+            .and_then(|candidates| candidates.first())
+        {
+            return *found;
+        }
+
+        let id = self.coordinates.len() as u32;
+        self.coordinates.push(coord);
+        self.cells.entry((cx, cy)).or_default().push(id);
+        id
+    }
+}
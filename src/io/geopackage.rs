@@ -0,0 +1,727 @@
+use super::geometry::{circuit_positions, Coordinate, EdgeGeometries};
+use super::{next_node_index, Attributes, GraphRepresentation};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::{anyhow, Context, Error};
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OpenFlags};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::str::FromStr;
+
+//Spatial reference system of the circuits written, WGS84 longitude and latitude
+const WGS84_SRS_ID: i32 = 4326;
+
+/// The table of a GeoPackage (or plain SQLite) file holding the edges of a graph, and the
+/// columns that make up the graph
+#[derive(Debug, Clone)]
+pub struct GeoPackageOptions {
+    pub table: String,
+    pub edge_from: String,
+    pub edge_to: String,
+    pub edge_weight: String,
+    /// Integer column identifying each edge; the table's rowid is used when `None`
+    pub edge_id: Option<String>,
+    /// Column holding each edge's LineString as a GeoPackage geometry (or plain WKB) blob
+    pub geometry: Option<String>,
+}
+
+impl Default for GeoPackageOptions {
+    fn default() -> Self {
+        Self {
+            table: "edges".to_string(),
+            edge_from: "from".to_string(),
+            edge_to: "to".to_string(),
+            edge_weight: "length".to_string(),
+            edge_id: None,
+            geometry: None,
+        }
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+//Reads the fields of a WKB geometry in the byte order it declares
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> WkbReader<'a> {
+    fn take<const SIZE: usize>(&mut self) -> Result<[u8; SIZE], Error> {
+        if self.bytes.len() < SIZE {
+            return Err(anyhow!("Truncated WKB geometry"));
+        }
+        let (head, rest) = self.bytes.split_at(SIZE);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn byte_order(&mut self) -> Result<(), Error> {
+        self.little_endian = match self.take::<1>()? {
+            [0] => false,
+            [1] => true,
+            [other] => return Err(anyhow!("Invalid WKB byte order {}", other)),
+        };
+        Ok(())
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take::<4>()?;
+        Ok(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        let bytes = self.take::<8>()?;
+        Ok(match self.little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        })
+    }
+
+    //Reads a LineString, or a MultiLineString of a single part, as ISO or EWKB
+    fn linestring(&mut self) -> Result<Vec<Coordinate>, Error> {
+        self.byte_order()?;
+        let geometry_type = self.u32()?;
+        let (base_type, has_z, has_m) = match geometry_type {
+            t if t & 0xe000_0000 != 0 => (t & 0xffff, t & 0x8000_0000 != 0, t & 0x4000_0000 != 0),
+            t => (
+                t % 1000,
+                matches!(t / 1000, 1 | 3),
+                matches!(t / 1000, 2 | 3),
+            ),
+        };
+
+        match base_type {
+            2 => (0..self.u32()?)
+                .map(|_| {
+                    let (lon, lat) = (self.f64()?, self.f64()?);
+                    let elevation = if has_z { Some(self.f64()?) } else { None };
+                    if has_m {
+                        self.f64()?;
+                    }
+                    Ok(Coordinate {
+                        lon,
+                        lat,
+                        elevation,
+                    })
+                })
+                .collect(),
+            5 => match self.u32()? {
+                1 => self.linestring(),
+                parts => Err(anyhow!(
+                    "MultiLineString geometries must have one part, not {}",
+                    parts
+                )),
+            },
+            other => Err(anyhow!(
+                "Expected a LineString geometry, not WKB type {}",
+                other
+            )),
+        }
+    }
+}
+
+/// Decodes a LineString from a GeoPackage geometry blob, or from plain WKB
+pub fn linestring_from_gpkg_blob(blob: &[u8]) -> Result<Vec<Coordinate>, Error> {
+    let wkb = match blob {
+        [b'G', b'P', _version, flags, ..] => {
+            //Empty geometries are flagged in the header, though their WKB is still present
+            if flags & 0b1_0000 != 0 {
+                return Ok(Vec::new());
+            }
+            let envelope_size = match (flags >> 1) & 0b111 {
+                0 => 0,
+                1 => 32,
+                2 | 3 => 48,
+                4 => 64,
+                other => return Err(anyhow!("Invalid GeoPackage envelope type {}", other)),
+            };
+            blob.get(8 + envelope_size..)
+                .ok_or_else(|| anyhow!("Truncated GeoPackage geometry"))?
+        }
+        _ => blob,
+    };
+
+    WkbReader {
+        bytes: wkb,
+        little_endian: true,
+    }
+    .linestring()
+}
+
+//Lines are written with Z values only when every one of their positions has an elevation
+fn has_elevations(positions: &[Coordinate]) -> bool {
+    !positions.is_empty() && positions.iter().all(|c| c.elevation.is_some())
+}
+
+/// Encodes positions as a GeoPackage geometry blob holding a little-endian WKB LineString,
+/// with Z values when every position has an elevation
+pub fn linestring_to_gpkg_blob(positions: &[Coordinate], srs_id: i32) -> Vec<u8> {
+    let with_z = has_elevations(positions);
+    let mut blob = vec![b'G', b'P', 0];
+
+    if positions.is_empty() {
+        //Little endian, no envelope, empty
+        blob.push(0b1_0001);
+        blob.extend(srs_id.to_le_bytes());
+    } else {
+        //Little endian, with an xy envelope
+        blob.push(0b0011);
+        blob.extend(srs_id.to_le_bytes());
+        let (min_x, max_x, min_y, max_y) = positions.iter().fold(
+            (
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_x, max_x, min_y, max_y), c| {
+                (
+                    min_x.min(c.lon),
+                    max_x.max(c.lon),
+                    min_y.min(c.lat),
+                    max_y.max(c.lat),
+                )
+            },
+        );
+        [min_x, max_x, min_y, max_y]
+            .iter()
+            .for_each(|v| blob.extend(v.to_le_bytes()));
+    }
+
+    blob.push(1);
+    blob.extend(if with_z { 1002u32 } else { 2u32 }.to_le_bytes());
+    blob.extend((positions.len() as u32).to_le_bytes());
+    for c in positions {
+        blob.extend(c.lon.to_le_bytes());
+        blob.extend(c.lat.to_le_bytes());
+        if let (true, Some(elevation)) = (with_z, c.elevation) {
+            blob.extend(elevation.to_le_bytes());
+        }
+    }
+    blob
+}
+
+fn json_value(value: ValueRef) -> Option<Value> {
+    match value {
+        ValueRef::Null => Some(Value::Null),
+        ValueRef::Integer(i) => Some(i.into()),
+        ValueRef::Real(r) => Some(r.into()),
+        ValueRef::Text(t) => Some(String::from_utf8_lossy(t).into()),
+        ValueRef::Blob(_) => None,
+    }
+}
+
+/// Reads a graph from the edge table of a GeoPackage (or plain SQLite) file, also returning
+/// the id of each edge, in the same order as the graph's `edge_list`
+///
+/// Rows are read one at a time, with nodes indexed as edges first name them. When a geometry
+/// column is given, each edge's LineString becomes its geometry and its ends give its nodes
+/// their coordinates. All other columns, except blobs, are kept as edge attributes.
+#[allow(clippy::type_complexity)]
+pub fn read_from_geopackage<N, E, Ix>(
+    path: &str,
+    options: &GeoPackageOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Vec<i64>), Error>
+where
+    N: FromStr + Eq + Hash + Copy + Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Opening {}", path))?;
+    let mut statement = connection
+        .prepare(&format!("SELECT rowid, * FROM {}", quote(&options.table)))
+        .with_context(|| format!("{}: reading table `{}`", path, options.table))?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect();
+    //The rowid comes first, and the table's own columns after it
+    let column = |name: &str| {
+        columns
+            .iter()
+            .skip(1)
+            .position(|column| column == name)
+            .map(|i| i + 1)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{}: table `{}` has no `{}` column",
+                    path,
+                    options.table,
+                    name
+                )
+            })
+    };
+    let from_column = column(&options.edge_from)?;
+    let to_column = column(&options.edge_to)?;
+    let weight_column = column(&options.edge_weight)?;
+    let id_column = match &options.edge_id {
+        Some(name) => column(name)?,
+        None => 0,
+    };
+    let geometry_column = options.geometry.as_deref().map(column).transpose()?;
+    let used = [
+        Some(0),
+        Some(from_column),
+        Some(to_column),
+        Some(weight_column),
+    ]
+    .into_iter()
+    .chain([Some(id_column), geometry_column])
+    .flatten()
+    .collect::<Vec<usize>>();
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+    let mut edge_geometries = EdgeGeometries::new();
+    let mut edge_ids = Vec::<i64>::new();
+
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid: i64 = row.get(0)?;
+        let what = |name: &str| format!("{}: `{}` of row {}", path, name, rowid);
+
+        let mut index_of = |column: usize| -> Result<Ix, Error> {
+            let label = match row.get_ref(column)? {
+                ValueRef::Integer(i) => i.to_string(),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+                _ => return Err(anyhow!("{} is not a node label", what(&columns[column]))),
+            };
+            let node_id = label.parse::<N>().map_err(|_| {
+                anyhow!("{} cannot be used as a node label", what(&columns[column]))
+            })?;
+            if let Some(ind) = node_weight_to_index.get(&node_id) {
+                return Ok(*ind);
+            }
+            let ind = next_node_index(&node_map)?;
+            node_map.insert(ind, node_id);
+            node_weight_to_index.insert(node_id, ind);
+            Ok(ind)
+        };
+        let u = index_of(from_column)?;
+        let v = index_of(to_column)?;
+        let w = match row.get_ref(weight_column)? {
+            ValueRef::Integer(i) => i as f64,
+            ValueRef::Real(r) => r,
+            _ => return Err(anyhow!("{} is not a number", what(&options.edge_weight))),
+        };
+        let id = match row.get_ref(id_column)? {
+            ValueRef::Integer(i) => i,
+            _ => return Err(anyhow!("{} is not an integer", what(&columns[id_column]))),
+        };
+
+        if let Some(column) = geometry_column {
+            if let ValueRef::Blob(blob) = row.get_ref(column)? {
+                let shape =
+                    linestring_from_gpkg_blob(blob).with_context(|| what(&columns[column]))?;
+                if let (Some(first), Some(last)) = (shape.first(), shape.last()) {
+                    coordinates.entry(u).or_insert(*first);
+                    coordinates.entry(v).or_insert(*last);
+                    edge_geometries.insert(edge_list.len(), shape);
+                }
+            }
+        }
+
+        edge_attributes.push(
+            (0..columns.len())
+                .filter(|i| !used.contains(i))
+                .filter_map(|i| {
+                    let value = json_value(row.get_ref(i).ok()?)?;
+                    Some((columns[i].clone(), value))
+                })
+                .collect(),
+        );
+        edge_list.push((u, v, E::from(w)));
+        edge_ids.push(id);
+    }
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(HashMap::new(), edge_attributes)
+            .with_edge_geometries(edge_geometries),
+        node_weight_to_index,
+        edge_ids,
+    ))
+}
+
+//Creates the tables every GeoPackage must have, if the file does not already have them
+fn initialize_geopackage(connection: &Connection) -> Result<(), Error> {
+    connection.execute_batch(
+        "PRAGMA application_id = 1196444487;
+        PRAGMA user_version = 10300;
+        CREATE TABLE IF NOT EXISTS gpkg_spatial_ref_sys (
+            srs_name TEXT NOT NULL,
+            srs_id INTEGER NOT NULL PRIMARY KEY,
+            organization TEXT NOT NULL,
+            organization_coordsys_id INTEGER NOT NULL,
+            definition TEXT NOT NULL,
+            description TEXT
+        );
+        INSERT OR IGNORE INTO gpkg_spatial_ref_sys VALUES
+            ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+            ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+            ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563,AUTHORITY[\"EPSG\",\"7030\"]],AUTHORITY[\"EPSG\",\"6326\"]],PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]],UNIT[\"degree\",0.0174532925199433,AUTHORITY[\"EPSG\",\"9122\"]],AUTHORITY[\"EPSG\",\"4326\"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+        CREATE TABLE IF NOT EXISTS gpkg_contents (
+            table_name TEXT NOT NULL PRIMARY KEY,
+            data_type TEXT NOT NULL,
+            identifier TEXT UNIQUE,
+            description TEXT DEFAULT '',
+            last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+            min_x DOUBLE,
+            min_y DOUBLE,
+            max_x DOUBLE,
+            max_y DOUBLE,
+            srs_id INTEGER,
+            CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+        );
+        CREATE TABLE IF NOT EXISTS gpkg_geometry_columns (
+            table_name TEXT NOT NULL,
+            column_name TEXT NOT NULL,
+            geometry_type_name TEXT NOT NULL,
+            srs_id INTEGER NOT NULL,
+            z TINYINT NOT NULL,
+            m TINYINT NOT NULL,
+            CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name),
+            CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+            CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id)
+        );",
+    )?;
+    Ok(())
+}
+
+/// Writes circuits to a new feature table of a GeoPackage, creating the file if need be, with
+/// one row per `(name, circuit)` pair holding its name, length, node count and LineString
+///
+/// Lines follow the shapes in `edge_geometries` where given. The table must not already
+/// exist, so that earlier results are never overwritten.
+pub fn write_circuits_to_geopackage<G>(
+    path: &str,
+    table: &str,
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+    G::EdgeWeight: Into<f64> + Copy,
+{
+    let lines = circuits
+        .iter()
+        .map(|(_name, circuit)| {
+            circuit_positions(
+                &circuit.ordered_node_weight_list,
+                &circuit.edge_list,
+                coordinates,
+                edge_geometries,
+            )
+        })
+        .collect::<Result<Vec<Vec<Coordinate>>, Error>>()?;
+    let all_positions = || lines.iter().flatten();
+    let bounds = |f: fn(&Coordinate) -> f64, min: bool| {
+        all_positions()
+            .map(f)
+            .reduce(if min { f64::min } else { f64::max })
+    };
+
+    //GeoPackage's z flag: 0 when no line has Z values, 1 when all do, and 2 when some do
+    let lines_with_z: Vec<bool> = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| has_elevations(line))
+        .collect();
+    let z = match (
+        lines_with_z.iter().all(|with_z| *with_z),
+        lines_with_z.iter().any(|with_z| *with_z),
+    ) {
+        (true, true) => 1,
+        (_, false) => 0,
+        (false, true) => 2,
+    };
+
+    let mut connection = Connection::open(path).with_context(|| format!("Opening {}", path))?;
+    let transaction = connection.transaction()?;
+    initialize_geopackage(&transaction)?;
+    let exists: bool = transaction.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = ?1)",
+        [table],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(anyhow!("{}: table `{}` already exists", path, table));
+    }
+    transaction
+        .execute_batch(&format!(
+            "CREATE TABLE {} (
+                fid INTEGER PRIMARY KEY AUTOINCREMENT,
+                circuit TEXT NOT NULL,
+                length DOUBLE,
+                node_count INTEGER,
+                geom LINESTRING
+            );",
+            quote(table)
+        ))
+        .with_context(|| format!("{}: creating table `{}`", path, table))?;
+    transaction.execute(
+        "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id)
+        VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            table,
+            bounds(|c| c.lon, true),
+            bounds(|c| c.lat, true),
+            bounds(|c| c.lon, false),
+            bounds(|c| c.lat, false),
+            WGS84_SRS_ID
+        ],
+    )?;
+    transaction.execute(
+        "INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', 'LINESTRING', ?2, ?3, 0)",
+        params![table, WGS84_SRS_ID, z],
+    )?;
+
+    {
+        let mut insert = transaction.prepare(&format!(
+            "INSERT INTO {} (circuit, length, node_count, geom) VALUES (?1, ?2, ?3, ?4)",
+            quote(table)
+        ))?;
+        for ((name, circuit), line) in circuits.iter().zip(&lines) {
+            let length: f64 = circuit.length.into();
+            insert.execute(params![
+                name,
+                length,
+                circuit.ordered_node_weight_list.len() as i64,
+                linestring_to_gpkg_blob(line, WGS84_SRS_ID)
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::stable_graph::StableDiGraph;
+    use tempfile::TempDir;
+
+    fn line(elevations: &[Option<f64>]) -> Vec<Coordinate> {
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, elevation)| Coordinate {
+                lon: -73.9 + i as f64 * 0.001,
+                lat: 40.7,
+                elevation: *elevation,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_blobs_with_and_without_z() {
+        let with_z = line(&[Some(3.0), Some(4.5)]);
+        let blob = linestring_to_gpkg_blob(&with_z, WGS84_SRS_ID);
+        assert_eq!(&blob[..4], &[b'G', b'P', 0, 0b0011]);
+        assert_eq!(linestring_from_gpkg_blob(&blob).unwrap(), with_z);
+
+        let without_z = line(&[None, None]);
+        let blob = linestring_to_gpkg_blob(&without_z, WGS84_SRS_ID);
+        assert_eq!(linestring_from_gpkg_blob(&blob).unwrap(), without_z);
+
+        //Z values are left out altogether when any position lacks one
+        let blob = linestring_to_gpkg_blob(&line(&[Some(3.0), None]), WGS84_SRS_ID);
+        assert_eq!(linestring_from_gpkg_blob(&blob).unwrap(), without_z);
+
+        let blob = linestring_to_gpkg_blob(&[], WGS84_SRS_ID);
+        assert_eq!(blob[3] & 0b1_0000, 0b1_0000);
+        assert!(linestring_from_gpkg_blob(&blob).unwrap().is_empty());
+    }
+
+    #[test]
+    fn skips_envelopes_of_every_size() {
+        let positions = line(&[None, None]);
+        let blob = linestring_to_gpkg_blob(&positions, WGS84_SRS_ID);
+        let wkb = &blob[8 + 32..];
+        assert_eq!(linestring_from_gpkg_blob(wkb).unwrap(), positions);
+
+        for (envelope_type, size) in [(0u8, 0), (1, 32), (2, 48), (3, 48), (4, 64)] {
+            let mut blob = vec![b'G', b'P', 0, 1 | envelope_type << 1];
+            blob.extend(WGS84_SRS_ID.to_le_bytes());
+            blob.extend(vec![0; size]);
+            blob.extend(wkb);
+            assert_eq!(linestring_from_gpkg_blob(&blob).unwrap(), positions);
+        }
+
+        let mut blob = vec![b'G', b'P', 0, 1 | 5 << 1];
+        blob.extend(WGS84_SRS_ID.to_le_bytes());
+        assert!(linestring_from_gpkg_blob(&blob).is_err());
+    }
+
+    fn edges_table(dir: &TempDir) -> String {
+        let path = dir.path().join("edges.gpkg");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                r#"CREATE TABLE edges (osm_id INTEGER, "from" INTEGER, "to" TEXT, length REAL,
+                    name TEXT, geom BLOB);"#,
+            )
+            .unwrap();
+        let shape = line(&[Some(3.0), Some(4.5), Some(6.0)]);
+        let rows = [
+            (
+                70,
+                1,
+                "2",
+                120.0,
+                Some(linestring_to_gpkg_blob(&shape, 4326)),
+            ),
+            (71, 2, "3", 80.5, None::<Vec<u8>>),
+        ];
+        for (id, from, to, length, geometry) in rows {
+            connection
+                .execute(
+                    "INSERT INTO edges VALUES (?1, ?2, ?3, ?4, 'Main', ?5)",
+                    params![id, from, to, length, geometry],
+                )
+                .unwrap();
+        }
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn reads_ids_and_geometries_from_the_named_columns() {
+        let dir = TempDir::new().unwrap();
+        let path = edges_table(&dir);
+        let options = GeoPackageOptions {
+            edge_id: Some("osm_id".to_string()),
+            geometry: Some("geom".to_string()),
+            ..Default::default()
+        };
+        let (gr, _, ids) = read_from_geopackage::<u64, f64, u32>(&path, &options).unwrap();
+
+        assert_eq!(ids, vec![70, 71]);
+        assert_eq!(gr.node_map, HashMap::from([(0, 1), (1, 2), (2, 3)]));
+        assert_eq!(gr.edge_list, vec![(0, 1, 120.0), (1, 2, 80.5)]);
+        assert_eq!(gr.edge_geometries[&0].len(), 3);
+        assert!(!gr.edge_geometries.contains_key(&1));
+        assert_eq!(gr.coordinates[&1].elevation, Some(6.0));
+        assert!(!gr.coordinates.contains_key(&2));
+        assert_eq!(
+            gr.edge_attributes[0],
+            Attributes::from_iter([("name".into(), "Main".into())])
+        );
+    }
+
+    #[test]
+    fn uses_rowids_and_keeps_other_columns_without_ids_or_geometries() {
+        let dir = TempDir::new().unwrap();
+        let path = edges_table(&dir);
+        let (gr, _, ids) =
+            read_from_geopackage::<u64, f64, u32>(&path, &GeoPackageOptions::default()).unwrap();
+
+        assert_eq!(ids, vec![1, 2]);
+        assert!(gr.coordinates.is_empty());
+        assert!(gr.edge_geometries.is_empty());
+        //Geometry blobs are not attributes, but the unused id column is
+        assert_eq!(gr.edge_attributes[1]["osm_id"], 71);
+        assert!(gr.edge_attributes[0].get("geom").is_none());
+        assert_eq!(gr.edge_attributes[1]["geom"], Value::Null);
+    }
+
+    fn circuit(nodes: Vec<u64>) -> EulerCircuit<StableDiGraph<u64, f64, u32>> {
+        EulerCircuit {
+            length: 250.0,
+            ordered_node_weight_list: nodes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_circuits_to_a_new_table_and_refuses_existing_ones() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("circuits.gpkg");
+        let path = path.to_str().unwrap();
+        let upper = circuit(vec![1, 2, 1]);
+        let lower = circuit(vec![1, 3, 1]);
+        let coordinates = HashMap::from([
+            (1, Coordinate::new(-73.9, 40.7).with_elevation(3.0)),
+            (2, Coordinate::new(-73.9, 40.701).with_elevation(4.0)),
+            (3, Coordinate::new(-73.899, 40.7)),
+        ]);
+        let circuits = [("upper", &upper), ("lower", &lower)];
+        write_circuits_to_geopackage(path, "runs", &circuits, &coordinates, &HashMap::new())
+            .unwrap();
+
+        let connection = Connection::open(path).unwrap();
+        let mut statement = connection
+            .prepare("SELECT circuit, length, node_count, geom FROM runs ORDER BY fid")
+            .unwrap();
+        let rows: Vec<(String, f64, i64, Vec<u8>)> = statement
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].0.as_str(), rows[0].1, rows[0].2),
+            ("upper", 250.0, 3)
+        );
+        let upper_line = linestring_from_gpkg_blob(&rows[0].3).unwrap();
+        assert_eq!(upper_line[1], coordinates[&2]);
+        let lower_line = linestring_from_gpkg_blob(&rows[1].3).unwrap();
+        assert_eq!(lower_line[1].elevation, None);
+
+        //Only the upper circuit has elevations throughout
+        let z: i64 = connection
+            .query_row(
+                "SELECT z FROM gpkg_geometry_columns WHERE table_name = 'runs'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(z, 2);
+        drop(statement);
+        drop(connection);
+
+        let err =
+            write_circuits_to_geopackage(path, "runs", &circuits, &coordinates, &HashMap::new())
+                .unwrap_err();
+        assert!(err.to_string().ends_with("table `runs` already exists"));
+
+        write_circuits_to_geopackage(
+            path,
+            "upper_run",
+            &circuits[..1],
+            &coordinates,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let z: i64 = Connection::open(path)
+            .unwrap()
+            .query_row(
+                "SELECT z FROM gpkg_geometry_columns WHERE table_name = 'upper_run'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(z, 1);
+    }
+}
@@ -0,0 +1,266 @@
+use super::geometry::{circuit_positions, Coordinate, EdgeGeometries};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::Error;
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+use std::fs;
+use std::hash::Hash;
+
+/// Whether circuits are written as GPX routes (`<rte>`) or tracks (`<trk>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpxKind {
+    Route,
+    Track,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpxOptions {
+    /// Name recorded in the document metadata
+    pub name: String,
+    pub kind: GpxKind,
+    /// Adds `Start` and `Finish` waypoints at the ends of each circuit
+    pub waypoints: bool,
+}
+
+impl Default for GpxOptions {
+    fn default() -> Self {
+        Self {
+            name: "speedicycle".to_string(),
+            kind: GpxKind::Route,
+            waypoints: false,
+        }
+    }
+}
+
+pub(super) fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn write_point(out: &mut String, tag: &str, coord: &Coordinate, name: Option<&str>) {
+    let _ = write!(
+        out,
+        "<{} lat=\"{:.7}\" lon=\"{:.7}\">",
+        tag, coord.lat, coord.lon
+    );
+    if let Some(elevation) = coord.elevation {
+        let _ = write!(out, "<ele>{:.2}</ele>", elevation);
+    }
+    if let Some(name) = name {
+        let _ = write!(out, "<name>{}</name>", escape_xml(name));
+    }
+    let _ = writeln!(out, "</{}>", tag);
+}
+
+/// Renders circuits as a GPX 1.1 document, one route or track per `(name, circuit)` pair,
+/// following the shapes in `edge_geometries` where given
+pub fn circuits_to_gpx<G>(
+    circuits: &[(&str, &EulerCircuit<G>)],
+    target_length: G::EdgeWeight,
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &GpxOptions,
+) -> Result<String, Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+    G::EdgeWeight: Into<f64> + Copy,
+{
+    let target_length: f64 = target_length.into();
+
+    //Resolve every position up front so that a missing coordinate fails the whole document
+    let positions = circuits
+        .iter()
+        .map(|(_name, circuit)| {
+            circuit_positions(
+                &circuit.ordered_node_weight_list,
+                &circuit.edge_list,
+                coordinates,
+                edge_geometries,
+            )
+        })
+        .collect::<Result<Vec<Vec<Coordinate>>, Error>>()?;
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(
+        "<gpx version=\"1.1\" creator=\"speedicycle\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    let _ = writeln!(
+        out,
+        "<metadata><name>{}</name></metadata>",
+        escape_xml(&options.name)
+    );
+
+    if options.waypoints {
+        for ((name, _circuit), points) in circuits.iter().zip(&positions) {
+            if let (Some(start), Some(finish)) = (points.first(), points.last()) {
+                write_point(&mut out, "wpt", start, Some(&format!("{} Start", name)));
+                write_point(&mut out, "wpt", finish, Some(&format!("{} Finish", name)));
+            }
+        }
+    }
+
+    for ((name, circuit), points) in circuits.iter().zip(&positions) {
+        let length: f64 = circuit.length.into();
+        let description = format!(
+            "Target length: {}, actual length: {} (deviation {})",
+            target_length,
+            length,
+            length - target_length
+        );
+        let (outer, point_tag) = match options.kind {
+            GpxKind::Route => ("rte", "rtept"),
+            GpxKind::Track => ("trk", "trkpt"),
+        };
+
+        let _ = writeln!(
+            out,
+            "<{}><name>{}</name><desc>{}</desc>",
+            outer,
+            escape_xml(name),
+            escape_xml(&description)
+        );
+        if options.kind == GpxKind::Track {
+            out.push_str("<trkseg>\n");
+        }
+        points
+            .iter()
+            .for_each(|coord| write_point(&mut out, point_tag, coord, None));
+        if options.kind == GpxKind::Track {
+            out.push_str("</trkseg>\n");
+        }
+        let _ = writeln!(out, "</{}>", outer);
+    }
+
+    out.push_str("</gpx>\n");
+    Ok(out)
+}
+
+pub fn write_circuits_to_gpx<G>(
+    path: &str,
+    circuits: &[(&str, &EulerCircuit<G>)],
+    target_length: G::EdgeWeight,
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &GpxOptions,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+    G::EdgeWeight: Into<f64> + Copy,
+{
+    fs::write(
+        path,
+        circuits_to_gpx(
+            circuits,
+            target_length,
+            coordinates,
+            edge_geometries,
+            options,
+        )?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::stable_graph::StableDiGraph;
+
+    type Circuit = EulerCircuit<StableDiGraph<u32, f64, u32>>;
+
+    fn circuit(nodes: Vec<u32>, length: f64) -> Circuit {
+        EulerCircuit {
+            length,
+            ordered_node_weight_list: nodes,
+            ..Default::default()
+        }
+    }
+
+    fn coordinates() -> HashMap<u32, Coordinate> {
+        HashMap::from([
+            (1, Coordinate::new(-73.9, 40.7).with_elevation(5.0)),
+            (2, Coordinate::new(-73.9, 40.701)),
+        ])
+    }
+
+    #[test]
+    fn writes_tracks_of_one_segment_with_latitude_first() {
+        let upper = circuit(vec![1, 2, 1], 230.0);
+        let options = GpxOptions {
+            kind: GpxKind::Track,
+            ..Default::default()
+        };
+        let gpx = circuits_to_gpx(
+            &[("upper", &upper)],
+            200.0,
+            &coordinates(),
+            &EdgeGeometries::new(),
+            &options,
+        )
+        .unwrap();
+        let lines: Vec<&str> = gpx.lines().collect();
+
+        assert_eq!(
+            lines[3..],
+            [
+                "<trk><name>upper</name><desc>Target length: 200, actual length: 230 (deviation 30)</desc>",
+                "<trkseg>",
+                "<trkpt lat=\"40.7000000\" lon=\"-73.9000000\"><ele>5.00</ele></trkpt>",
+                "<trkpt lat=\"40.7010000\" lon=\"-73.9000000\"></trkpt>",
+                "<trkpt lat=\"40.7000000\" lon=\"-73.9000000\"><ele>5.00</ele></trkpt>",
+                "</trkseg>",
+                "</trk>",
+                "</gpx>",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_routes_and_waypoints_with_escaped_names() {
+        let upper = circuit(vec![1, 2, 1], 230.0);
+        let lower = circuit(vec![2, 1, 2], 190.0);
+        let options = GpxOptions {
+            name: "Run \"A\" & <B>".to_string(),
+            waypoints: true,
+            ..Default::default()
+        };
+        let gpx = circuits_to_gpx(
+            &[("Tom's <loop>", &upper), ("lower", &lower)],
+            200.0,
+            &coordinates(),
+            &EdgeGeometries::new(),
+            &options,
+        )
+        .unwrap();
+
+        assert!(gpx.contains("<metadata><name>Run &quot;A&quot; &amp; &lt;B&gt;</name></metadata>"));
+        assert!(gpx.contains(
+            "<wpt lat=\"40.7000000\" lon=\"-73.9000000\"><ele>5.00</ele><name>Tom&apos;s &lt;loop&gt; Start</name></wpt>"
+        ));
+        assert!(gpx.contains("<rte><name>Tom&apos;s &lt;loop&gt;</name>"));
+        assert!(gpx.contains("<name>lower Finish</name>"));
+        assert_eq!(gpx.matches("<rte>").count(), 2);
+        assert_eq!(gpx.matches("<rtept ").count(), 6);
+        assert!(!gpx.contains("<trkseg>"));
+
+        //The document must still be well formed
+        #[cfg(feature = "xml")]
+        {
+            let mut reader = quick_xml::Reader::from_str(&gpx);
+            loop {
+                match reader.read_event().unwrap() {
+                    quick_xml::events::Event::Eof => break,
+                    _ => continue,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,668 @@
+#[cfg(feature = "xml")]
+use super::compression::open_input;
+#[cfg(feature = "xml")]
+use super::geometry::geodesic_length;
+use super::geometry::Coordinate;
+use super::gpx::escape_xml;
+#[cfg(feature = "xml")]
+use super::GraphRepresentation;
+use super::{Attributes, GraphAttributes};
+use crate::graph::{euler::EulerCircuit, input_edge};
+
+#[cfg(feature = "xml")]
+use anyhow::anyhow;
+use anyhow::Error;
+use petgraph::stable_graph::{IndexType, StableDiGraph};
+#[cfg(feature = "xml")]
+use quick_xml::escape::resolve_predefined_entity;
+#[cfg(feature = "xml")]
+use quick_xml::events::{BytesStart, Event};
+#[cfg(feature = "xml")]
+use quick_xml::{Reader, XmlVersion};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Write};
+use std::fs;
+use std::hash::Hash;
+#[cfg(feature = "xml")]
+use std::str::FromStr;
+
+/// Names of the GraphML attributes (as declared by `<key attr.name=...>`) that make up the graph
+#[derive(Debug, Clone)]
+pub struct GraphMlOptions {
+    /// Node attribute holding each node's label; the node's `id` is used when `None`
+    pub node_label: Option<String>,
+    pub edge_weight: String,
+    /// Weight of edges without an `edge_weight` attribute whose endpoints lack coordinates;
+    /// edges between positioned nodes are given their geodesic length in meters instead
+    pub default_weight: f64,
+    /// Nodes are only given coordinates when both the latitude and longitude attributes are present
+    pub latitude: String,
+    pub longitude: String,
+    pub elevation: String,
+}
+
+impl Default for GraphMlOptions {
+    fn default() -> Self {
+        Self {
+            node_label: None,
+            edge_weight: "weight".to_string(),
+            default_weight: 1.0,
+            latitude: "lat".to_string(),
+            longitude: "lon".to_string(),
+            elevation: "elevation".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "xml")]
+struct GraphMlKey {
+    id: String,
+    domain: String,
+    name: String,
+    default: Option<String>,
+}
+
+//A node or edge as read from the document, with its `<data>` values keyed by key id
+#[cfg(feature = "xml")]
+struct GraphMlElement {
+    id: String,
+    endpoints: Option<(String, String)>,
+    data: HashMap<String, String>,
+}
+
+#[cfg(feature = "xml")]
+fn attribute(e: &BytesStart, key: &str) -> Result<Option<String>, Error> {
+    for attr in e.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == key {
+            return Ok(Some(
+                attr.normalized_value(XmlVersion::Implicit1_0)?.into_owned(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "xml")]
+fn required_attribute(e: &BytesStart, key: &str) -> Result<String, Error> {
+    attribute(e, key)?
+        .ok_or_else(|| anyhow!("<{}> element is missing `{}`", e.name().as_ref(), key))
+}
+
+//Looks up an attribute by name, falling back to the default declared for its key
+#[cfg(feature = "xml")]
+fn value<'a>(
+    keys: &'a [GraphMlKey],
+    domain: &str,
+    element: &'a GraphMlElement,
+    name: &str,
+) -> Option<&'a str> {
+    keys.iter()
+        .filter(|key| key.name == name && (key.domain == domain || key.domain == "all"))
+        .find_map(|key| element.data.get(&key.id).or(key.default.as_ref()))
+        .map(|val| val.as_str())
+}
+
+//Every attribute of the element, including defaults, other than those named in `used`
+#[cfg(feature = "xml")]
+fn remaining_values(
+    keys: &[GraphMlKey],
+    domain: &str,
+    element: &GraphMlElement,
+    used: &[&str],
+) -> Attributes {
+    keys.iter()
+        .filter(|key| key.domain == domain || key.domain == "all")
+        .filter(|key| !used.contains(&key.name.as_str()))
+        .filter_map(|key| {
+            value(keys, domain, element, &key.name).map(|val| (key.name.clone(), val.into()))
+        })
+        .collect()
+}
+
+#[cfg(feature = "xml")]
+fn parsed_value<T: FromStr>(
+    keys: &[GraphMlKey],
+    domain: &str,
+    element: &GraphMlElement,
+    name: &str,
+) -> Result<Option<T>, Error> {
+    value(keys, domain, element, name)
+        .map(|val| {
+            val.trim().parse::<T>().map_err(|_| {
+                anyhow!(
+                    "Invalid `{}` value `{}` on {} {}",
+                    name,
+                    val,
+                    domain,
+                    element.id
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Reads a graph from a GraphML document, as written by yEd, Gephi or NetworkX
+///
+/// Edges are treated as undirected whatever the graph's `edgedefault`. Nested graphs are
+/// flattened and hyperedges are ignored. All other attributes are kept, as strings, as node
+/// and edge attributes.
+///
+/// Without a `node_label` attribute, nodes are labelled by their ids when every id can be
+/// parsed as a label, as with numeric ids. Otherwise, as with the `n0`, `n1`, ... ids of
+/// most editors, nodes are labelled by their position in the document and keep their
+/// original id as an `id` attribute.
+#[cfg(feature = "xml")]
+#[allow(clippy::type_complexity)]
+pub fn read_from_graphml<N, E, Ix>(
+    filepath: &str,
+    options: &GraphMlOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: FromStr + Eq + Hash + Copy + TryFrom<u32>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut reader = Reader::from_reader(open_input(filepath)?);
+    let mut buf = Vec::new();
+
+    let mut keys = Vec::<GraphMlKey>::new();
+    let mut nodes = Vec::<GraphMlElement>::new();
+    let mut edges = Vec::<GraphMlElement>::new();
+    //Nodes and edges whose end tag has not yet been reached; a node may hold a nested graph
+    let mut open = Vec::<GraphMlElement>::new();
+    //Key id of the `<data>` or `<default>` element being read, and its text so far
+    let mut text: Option<(String, String)> = None;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                match e.name().as_ref() {
+                    "key" => keys.push(GraphMlKey {
+                        id: required_attribute(e, "id")?,
+                        domain: attribute(e, "for")?.unwrap_or_else(|| "all".to_string()),
+                        name: match attribute(e, "attr.name")? {
+                            Some(name) => name,
+                            None => required_attribute(e, "id")?,
+                        },
+                        default: None,
+                    }),
+                    "default" if !is_empty => text = Some((String::new(), String::new())),
+                    "node" => {
+                        let node = GraphMlElement {
+                            id: required_attribute(e, "id")?,
+                            endpoints: None,
+                            data: HashMap::new(),
+                        };
+                        if is_empty {
+                            nodes.push(node);
+                        } else {
+                            open.push(node);
+                        }
+                    }
+                    "edge" => {
+                        let source = required_attribute(e, "source")?;
+                        let target = required_attribute(e, "target")?;
+                        let edge = GraphMlElement {
+                            id: attribute(e, "id")?
+                                .unwrap_or_else(|| format!("{}-{}", source, target)),
+                            endpoints: Some((source, target)),
+                            data: HashMap::new(),
+                        };
+                        if is_empty {
+                            edges.push(edge);
+                        } else {
+                            open.push(edge);
+                        }
+                    }
+                    "data" if !is_empty => {
+                        text = Some((required_attribute(e, "key")?, String::new()));
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(e) => {
+                if let Some((_, content)) = text.as_mut() {
+                    content.push_str(&e.xml10_content());
+                }
+            }
+            Event::CData(e) => {
+                if let Some((_, content)) = text.as_mut() {
+                    content.push_str(&e.xml10_content());
+                }
+            }
+            Event::GeneralRef(e) => {
+                if let Some((_, content)) = text.as_mut() {
+                    match e.resolve_char_ref()? {
+                        Some(c) => content.push(c),
+                        None => content.push_str(
+                            resolve_predefined_entity(&e.xml10_content()).ok_or_else(|| {
+                                anyhow!("Unknown entity `&{};`", e.xml10_content())
+                            })?,
+                        ),
+                    }
+                }
+            }
+            Event::End(e) => match e.name().as_ref() {
+                "default" => {
+                    if let (Some((_, content)), Some(key)) = (text.take(), keys.last_mut()) {
+                        key.default = Some(content);
+                    }
+                }
+                "data" => {
+                    if let (Some((key, content)), Some(element)) = (text.take(), open.last_mut()) {
+                        element.data.insert(key, content);
+                    }
+                }
+                "node" | "edge" => {
+                    if let Some(element) = open.pop() {
+                        match element.endpoints {
+                            Some(_) => edges.push(element),
+                            None => nodes.push(element),
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+    let mut id_to_index = HashMap::<&str, Ix>::new();
+    let mut node_attributes = HashMap::<Ix, Attributes>::new();
+    let used_node_keys: Vec<&str> = [
+        options.node_label.as_deref(),
+        Some(options.latitude.as_str()),
+        Some(options.longitude.as_str()),
+        Some(options.elevation.as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    //Ids that are not all labels are replaced by positions, so every label stays unique
+    let id_labels: Option<Vec<N>> = match options.node_label {
+        Some(_) => None,
+        None => nodes.iter().map(|node| node.id.parse::<N>().ok()).collect(),
+    };
+
+    for (i, node) in (0..).zip(&nodes) {
+        let label = match (&options.node_label, &id_labels) {
+            (Some(name), _) => parsed_value::<N>(&keys, "node", node, name)?
+                .ok_or_else(|| anyhow!("Node {} has no `{}` attribute", node.id, name))?,
+            (None, Some(labels)) => labels[i as usize],
+            (None, None) => N::try_from(i).unwrap(),
+        };
+        if id_to_index.contains_key(node.id.as_str()) {
+            return Err(anyhow!("Duplicate node id {}", node.id));
+        }
+        let ind = Ix::try_from(i).unwrap();
+
+        let lat = parsed_value::<f64>(&keys, "node", node, &options.latitude)?;
+        let lon = parsed_value::<f64>(&keys, "node", node, &options.longitude)?;
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            let mut coord = Coordinate::new(lon, lat);
+            if let Some(elevation) = parsed_value::<f64>(&keys, "node", node, &options.elevation)? {
+                coord = coord.with_elevation(elevation);
+            }
+            coordinates.insert(ind, coord);
+        }
+
+        id_to_index.insert(&node.id, ind);
+        let mut attributes = remaining_values(&keys, "node", node, &used_node_keys);
+        if options.node_label.is_none() && id_labels.is_none() {
+            attributes.insert("id".to_string(), node.id.clone().into());
+        }
+        node_attributes.insert(ind, attributes);
+        node_map.insert(ind, label);
+        node_weight_to_index.insert(label, ind);
+    }
+
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+    for edge in &edges {
+        let (source, target) = edge.endpoints.as_ref().unwrap();
+        let index_of = |id: &String| {
+            id_to_index
+                .get(id.as_str())
+                .copied()
+                .ok_or_else(|| anyhow!("Edge {} refers to unknown node {}", edge.id, id))
+        };
+        let (u, v) = (index_of(source)?, index_of(target)?);
+        let w = match parsed_value::<f64>(&keys, "edge", edge, &options.edge_weight)? {
+            Some(w) => w,
+            None => match (coordinates.get(&u), coordinates.get(&v)) {
+                (Some(from), Some(to)) => geodesic_length(&[*from, *to]),
+                _ => options.default_weight,
+            },
+        };
+        edge_list.push((u, v, E::from(w)));
+        edge_attributes.push(remaining_values(
+            &keys,
+            "edge",
+            edge,
+            &[options.edge_weight.as_str()],
+        ));
+    }
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(node_attributes, edge_attributes),
+        node_weight_to_index,
+    ))
+}
+
+//The GraphML type of an attribute value, or `None` for values that are left out
+fn attribute_type(val: &Value) -> Option<&'static str> {
+    match val {
+        Value::Null => None,
+        Value::Bool(_) => Some("boolean"),
+        Value::Number(n) if n.is_i64() || n.is_u64() => Some("long"),
+        Value::Number(_) => Some("double"),
+        _ => Some("string"),
+    }
+}
+
+//The name and type of every attribute in `sets`, in name order; an attribute whose values
+//differ in type is declared as a double if they are all numbers, or else a string
+fn attribute_keys<'a>(
+    sets: impl Iterator<Item = &'a Attributes>,
+    reserved: impl Fn(&str) -> bool,
+) -> Vec<(String, &'static str)> {
+    let mut keys = BTreeMap::<String, &'static str>::new();
+    sets.flat_map(|attrs| attrs.iter())
+        .filter(|(name, _)| !reserved(name))
+        .for_each(|(name, val)| {
+            if let Some(kind) = attribute_type(val) {
+                let declared = keys.entry(name.clone()).or_insert(kind);
+                *declared = match (*declared, kind) {
+                    (a, b) if a == b => a,
+                    ("long" | "double", "long" | "double") => "double",
+                    _ => "string",
+                };
+            }
+        });
+    keys.into_iter().collect()
+}
+
+fn write_attribute_data(
+    out: &mut String,
+    prefix: &str,
+    keys: &[(String, &'static str)],
+    attrs: &Attributes,
+) {
+    keys.iter().enumerate().for_each(|(i, (name, _))| {
+        let text = match attrs.get(name) {
+            None | Some(Value::Null) => return,
+            Some(Value::String(s)) => s.clone(),
+            Some(val) => val.to_string(),
+        };
+        let _ = write!(
+            out,
+            "<data key=\"{}{}\">{}</data>",
+            prefix,
+            i,
+            escape_xml(&text)
+        );
+    });
+}
+
+/// Renders a graph built by `make_graph` (or the trimmed graph of a `BandhariGraph`) as
+/// an undirected GraphML document
+///
+/// Nodes are written with their labels as ids, along with their positions and any node
+/// and edge attributes read from the input. Each `(name, circuit)` pair adds a boolean
+/// edge attribute called `name`, set on the edges the circuit traverses.
+#[allow(clippy::type_complexity)]
+pub fn graph_to_graphml<N, E, Ix>(
+    graph: &StableDiGraph<N, E, Ix>,
+    circuits: &[(&str, &EulerCircuit<StableDiGraph<N, E, Ix>>)],
+    coordinates: &HashMap<N, Coordinate>,
+    attributes: &GraphAttributes<N>,
+    options: &GraphMlOptions,
+) -> String
+where
+    N: Display + Eq + Hash,
+    E: Into<f64> + Copy,
+    Ix: IndexType,
+{
+    let walked: Vec<HashSet<usize>> = circuits
+        .iter()
+        .map(|(_, circuit)| circuit.edge_list.iter().map(|edge| edge.id).collect())
+        .collect();
+    let has_elevation = coordinates.values().any(|c| c.elevation.is_some());
+
+    //Input attributes are declared after the graph's own, skipping any they would shadow
+    let node_reserved = [
+        options.node_label.as_deref(),
+        Some(options.latitude.as_str()),
+        Some(options.longitude.as_str()),
+        Some(options.elevation.as_str()),
+    ];
+    let node_keys = attribute_keys(
+        graph
+            .node_indices()
+            .filter_map(|ind| attributes.node(&graph[ind])),
+        |name| node_reserved.contains(&Some(name)),
+    );
+    let edge_keys = attribute_keys(
+        graph
+            .edge_indices()
+            .filter_map(|e| attributes.edge(input_edge(e).id)),
+        |name| name == options.edge_weight || circuits.iter().any(|(c, _)| *c == name),
+    );
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    let mut declare = |id: &str, domain: &str, name: &str, kind: &str, default: Option<&str>| {
+        let _ = write!(
+            out,
+            "<key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"",
+            id,
+            domain,
+            escape_xml(name),
+            kind
+        );
+        let _ = match default {
+            Some(default) => writeln!(out, "><default>{}</default></key>", default),
+            None => writeln!(out, "/>"),
+        };
+    };
+    if let Some(name) = &options.node_label {
+        declare("label", "node", name, "string", None);
+    }
+    if !coordinates.is_empty() {
+        declare("lat", "node", &options.latitude, "double", None);
+        declare("lon", "node", &options.longitude, "double", None);
+    }
+    if has_elevation {
+        declare("elevation", "node", &options.elevation, "double", None);
+    }
+    declare("weight", "edge", &options.edge_weight, "double", None);
+    for (i, (name, _)) in circuits.iter().enumerate() {
+        declare(&format!("c{}", i), "edge", name, "boolean", Some("false"));
+    }
+    for (i, (name, kind)) in node_keys.iter().enumerate() {
+        declare(&format!("n{}", i), "node", name, kind, None);
+    }
+    for (i, (name, kind)) in edge_keys.iter().enumerate() {
+        declare(&format!("e{}", i), "edge", name, kind, None);
+    }
+    out.push_str("<graph edgedefault=\"undirected\">\n");
+
+    graph.node_indices().for_each(|ind| {
+        let label = escape_xml(&graph[ind].to_string());
+        let _ = write!(out, "<node id=\"{}\">", label);
+        if options.node_label.is_some() {
+            let _ = write!(out, "<data key=\"label\">{}</data>", label);
+        }
+        if let Some(coord) = coordinates.get(&graph[ind]) {
+            let _ = write!(
+                out,
+                "<data key=\"lat\">{}</data><data key=\"lon\">{}</data>",
+                coord.lat, coord.lon
+            );
+            if let Some(elevation) = coord.elevation {
+                let _ = write!(out, "<data key=\"elevation\">{}</data>", elevation);
+            }
+        }
+        if let Some(attrs) = attributes.node(&graph[ind]) {
+            write_attribute_data(&mut out, "n", &node_keys, attrs);
+        }
+        out.push_str("</node>\n");
+    });
+
+    //make_graph stores each undirected edge as a pair of opposing arcs, so only the arc
+    //running in the input direction is written
+    graph.edge_indices().for_each(|e| {
+        let edge = input_edge(e);
+        if !edge.forward {
+            return;
+        }
+        let (u, v) = graph.edge_endpoints(e).unwrap();
+
+        let w: f64 = graph[e].into();
+        let _ = write!(
+            out,
+            "<edge source=\"{}\" target=\"{}\"><data key=\"weight\">{}</data>",
+            escape_xml(&graph[u].to_string()),
+            escape_xml(&graph[v].to_string()),
+            w
+        );
+        walked.iter().enumerate().for_each(|(i, ids)| {
+            if ids.contains(&edge.id) {
+                let _ = write!(out, "<data key=\"c{}\">true</data>", i);
+            }
+        });
+        if let Some(attrs) = attributes.edge(edge.id) {
+            write_attribute_data(&mut out, "e", &edge_keys, attrs);
+        }
+        out.push_str("</edge>\n");
+    });
+
+    out.push_str("</graph>\n</graphml>\n");
+    out
+}
+
+#[allow(clippy::type_complexity)]
+pub fn write_graph_to_graphml<N, E, Ix>(
+    path: &str,
+    graph: &StableDiGraph<N, E, Ix>,
+    circuits: &[(&str, &EulerCircuit<StableDiGraph<N, E, Ix>>)],
+    coordinates: &HashMap<N, Coordinate>,
+    attributes: &GraphAttributes<N>,
+    options: &GraphMlOptions,
+) -> Result<(), Error>
+where
+    N: Display + Eq + Hash,
+    E: Into<f64> + Copy,
+    Ix: IndexType,
+{
+    fs::write(
+        path,
+        graph_to_graphml(graph, circuits, coordinates, attributes, options),
+    )?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "xml"))]
+mod tests {
+    use super::*;
+    use crate::graph::make_graph;
+    use std::io::Write as _;
+    use tempfile::NamedTempFile;
+
+    const EDITOR_GRAPHML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="lat" attr.type="double"/>
+  <key id="d1" for="node" attr.name="lon" attr.type="double"/>
+  <key id="d2" for="edge" attr.name="weight" attr.type="double"/>
+  <key id="d3" for="edge" attr.name="name" attr.type="string"/>
+  <key id="d4" for="node" attr.name="kind" attr.type="string"/>
+  <graph edgedefault="undirected">
+    <node id="n0"><data key="d0">40.7</data><data key="d1">-73.9</data></node>
+    <node id="n1"><data key="d0">40.701</data><data key="d1">-73.9</data></node>
+    <node id="n2"><data key="d4">junction</data></node>
+    <edge source="n0" target="n1"><data key="d3">Main &amp; 1st</data></edge>
+    <edge source="n1" target="n2"/>
+    <edge source="n2" target="n0"><data key="d2">7.5</data></edge>
+  </graph>
+</graphml>
+"#;
+
+    fn read(contents: &str) -> (GraphRepresentation<u32, f64, u32>, HashMap<u32, u32>) {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        read_from_graphml(file.path().to_str().unwrap(), &GraphMlOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn labels_nodes_by_position_when_ids_are_not_labels() {
+        let (gr, node_weight_to_index) = read(EDITOR_GRAPHML);
+
+        assert_eq!(gr.node_map, HashMap::from([(0, 0), (1, 1), (2, 2)]));
+        assert_eq!(node_weight_to_index[&2], 2);
+        assert_eq!(gr.node_attributes[&0]["id"], "n0");
+        assert_eq!(gr.node_attributes[&2]["kind"], "junction");
+    }
+
+    #[test]
+    fn labels_nodes_by_numeric_ids() {
+        let (gr, _) = read(
+            r#"<graphml><graph><node id="12"/><node id="7"/>
+            <edge source="12" target="7"><data key="weight">2</data></edge></graph></graphml>"#,
+        );
+
+        assert_eq!(gr.node_map, HashMap::from([(0, 12), (1, 7)]));
+        assert!(!gr.node_attributes[&0].contains_key("id"));
+    }
+
+    #[test]
+    fn falls_back_to_geodesic_length_then_the_default_weight() {
+        let (gr, _) = read(EDITOR_GRAPHML);
+        let weights: Vec<f64> = gr.edge_list.iter().map(|(_, _, w)| *w).collect();
+
+        assert!((weights[0] - 111.2).abs() < 0.5, "{}", weights[0]);
+        assert_eq!(weights[1], 1.0);
+        assert_eq!(weights[2], 7.5);
+    }
+
+    #[test]
+    fn writes_input_attributes_back_out() {
+        let (gr, _) = read(EDITOR_GRAPHML);
+        let coordinates = gr.node_coordinates();
+        let attributes = gr.attributes();
+        let (graph, _) = make_graph::<&'static StableDiGraph<u32, f64, u32>, u32>(gr);
+
+        let out = graph_to_graphml(
+            &graph,
+            &[],
+            &coordinates,
+            &attributes,
+            &GraphMlOptions::default(),
+        );
+        assert!(out.contains(r#"attr.name="name" attr.type="string""#));
+        assert!(out.contains(r#"attr.name="id" attr.type="string""#));
+        assert!(out.contains("Main &amp; 1st"));
+
+        let (reread, node_weight_to_index) = read(&out);
+        assert_eq!(reread.edge_attributes[0]["name"], "Main & 1st");
+        assert_eq!(
+            reread.node_attributes[&node_weight_to_index[&2]]["kind"],
+            "junction"
+        );
+        assert_eq!(reread.edge_list[2].2, 7.5);
+    }
+}
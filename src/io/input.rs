@@ -0,0 +1,686 @@
+use super::compression::open_input;
+#[cfg(feature = "osm-pbf")]
+use super::read_from_osm_pbf;
+#[cfg(feature = "csv")]
+use super::{read_from_csv, CsvOptions};
+use super::{
+    read_from_dimacs, read_from_dimacs_gr, read_from_edges_json_file, read_from_geojson_file,
+    read_from_node_link_file, read_from_overpass_json, DimacsCoOptions, GeoJsonOptions,
+    GraphMlOptions, GraphRepresentation, NodeLinkOptions, OsmTagFilter,
+};
+#[cfg(feature = "geopackage")]
+use super::{read_from_geopackage, GeoPackageOptions};
+#[cfg(feature = "xml")]
+use super::{read_from_graphml, read_from_osm_xml};
+#[cfg(feature = "shapefile")]
+use super::{read_from_shapefile, ShapefileOptions};
+
+use anyhow::{anyhow, Context, Error};
+use serde::Deserialize;
+use std::fmt::{self, Debug, Display};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+//Enough of a file to find the header or top-level keys of any supported format
+const SNIFF_LENGTH: u64 = 8192;
+
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+const SHAPEFILE_MAGIC: &[u8] = &[0x00, 0x00, 0x27, 0x0a];
+
+/// The graph file formats `read_graph` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// The DIMACS dialect described in the README (`p`, `v` and `e` lines)
+    Dimacs,
+    /// 9th DIMACS Implementation Challenge shortest-path format (`p sp` and `a` lines)
+    DimacsGr,
+    /// JSON array of `EdgeRepresentation`s
+    EdgesJson,
+    GeoJson,
+    /// NetworkX `node_link_data` JSON
+    NodeLink,
+    /// Saved Overpass API JSON response
+    OverpassJson,
+    GraphMl,
+    OsmXml,
+    OsmPbf,
+    /// GeoPackage or SQLite file with an `edges` table
+    GeoPackage,
+    Shapefile,
+    /// CSV edge list with `from`, `to` and `length` columns
+    Csv,
+}
+
+impl InputFormat {
+    pub const ALL: [InputFormat; 12] = [
+        InputFormat::Dimacs,
+        InputFormat::DimacsGr,
+        InputFormat::EdgesJson,
+        InputFormat::GeoJson,
+        InputFormat::NodeLink,
+        InputFormat::OverpassJson,
+        InputFormat::GraphMl,
+        InputFormat::OsmXml,
+        InputFormat::OsmPbf,
+        InputFormat::GeoPackage,
+        InputFormat::Shapefile,
+        InputFormat::Csv,
+    ];
+
+    fn from_extension(extension: &str) -> Option<InputFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gr" => Some(InputFormat::DimacsGr),
+            "dimacs" => Some(InputFormat::Dimacs),
+            //The other JSON formats are told apart by their contents
+            "json" => Some(InputFormat::EdgesJson),
+            "geojson" => Some(InputFormat::GeoJson),
+            "graphml" => Some(InputFormat::GraphMl),
+            "osm" => Some(InputFormat::OsmXml),
+            "pbf" => Some(InputFormat::OsmPbf),
+            "gpkg" | "sqlite" | "db" => Some(InputFormat::GeoPackage),
+            "shp" => Some(InputFormat::Shapefile),
+            "csv" => Some(InputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Options for each of the readers `read_graph` may choose between
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    pub dimacs_co: DimacsCoOptions,
+    pub geojson: GeoJsonOptions,
+    pub node_link: NodeLinkOptions,
+    /// Used for OSM XML, OSM PBF and Overpass JSON alike
+    pub osm: OsmTagFilter,
+    pub graphml: GraphMlOptions,
+    #[cfg(feature = "geopackage")]
+    pub geopackage: GeoPackageOptions,
+    #[cfg(feature = "shapefile")]
+    pub shapefile: ShapefileOptions,
+    #[cfg(feature = "csv")]
+    pub csv: CsvOptions,
+    /// CSV nodes file giving the ids, and optionally the positions, of the nodes named by a
+    /// CSV edges file
+    #[cfg(feature = "csv")]
+    pub csv_nodes_path: Option<String>,
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InputFormat::Dimacs => "DIMACS",
+            InputFormat::DimacsGr => "DIMACS shortest path (.gr)",
+            InputFormat::EdgesJson => "edges JSON",
+            InputFormat::GeoJson => "GeoJSON",
+            InputFormat::NodeLink => "node-link JSON",
+            InputFormat::OverpassJson => "Overpass JSON",
+            InputFormat::GraphMl => "GraphML",
+            InputFormat::OsmXml => "OSM XML",
+            InputFormat::OsmPbf => "OSM PBF",
+            InputFormat::GeoPackage => "GeoPackage",
+            InputFormat::Shapefile => "Shapefile",
+            InputFormat::Csv => "CSV",
+        })
+    }
+}
+
+//The path without a `.gz` or `.zst` extension, whose own extension names the contents
+fn contents_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    match path.extension() {
+        Some(ext) if ext == "gz" || ext == "zst" => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+//The `.co` coordinate file that accompanies a DIMACS file, compressed or not
+fn companion_co_path(path: &str) -> Option<PathBuf> {
+    ["co", "co.gz", "co.zst"]
+        .into_iter()
+        .map(|ext| contents_path(path).with_extension(ext))
+        .find(|co_path| co_path.exists())
+}
+
+//Every file besides `path` that reading it may open, whichever format it turns out to be in:
+//a DIMACS `.co` coordinate file, a Shapefile's `.dbf` and `.prj`, and the CSV nodes file
+#[cfg_attr(not(feature = "csv"), allow(unused_variables))]
+pub(crate) fn companion_paths(path: &str, options: &InputOptions) -> Vec<PathBuf> {
+    let shapefile_paths = ["dbf", "prj"]
+        .into_iter()
+        .map(|ext| Path::new(path).with_extension(ext))
+        .filter(|companion| companion.exists());
+    #[cfg(feature = "csv")]
+    let nodes_paths = options.csv_nodes_path.iter().map(PathBuf::from);
+    #[cfg(not(feature = "csv"))]
+    let nodes_paths = std::iter::empty();
+    companion_co_path(path)
+        .into_iter()
+        .chain(shapefile_paths)
+        .chain(nodes_paths)
+        .collect()
+}
+
+fn read_head(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    reader.take(SNIFF_LENGTH).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn sniff_text(head: &str) -> Vec<InputFormat> {
+    let text = head.trim_start_matches('\u{feff}').trim_start();
+    match text.chars().next() {
+        Some('[') => vec![InputFormat::EdgesJson],
+        Some('{') => [
+            (text.contains("\"FeatureCollection\""), InputFormat::GeoJson),
+            (text.contains("\"elements\""), InputFormat::OverpassJson),
+            (
+                text.contains("\"nodes\"")
+                    && (text.contains("\"links\"") || text.contains("\"edges\"")),
+                InputFormat::NodeLink,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(matched, format)| matched.then_some(format))
+        .collect(),
+        Some('<') if text.contains("<graphml") => vec![InputFormat::GraphMl],
+        Some('<') if text.contains("<osm") => vec![InputFormat::OsmXml],
+        _ => {
+            //DIMACS files open with their `p` header, after any comments
+            let header = text
+                .lines()
+                .map(str::trim)
+                .find(|line| !(line.is_empty() || line == &"c" || line.starts_with("c ")));
+            match header.map(|line| line.split_whitespace().collect::<Vec<_>>()) {
+                Some(fields) if fields.starts_with(&["p", "sp"]) => vec![InputFormat::DimacsGr],
+                //`p aux` headers belong to `.co` coordinate files, which hold no edges
+                Some(fields) if fields.starts_with(&["p", "aux"]) => Vec::new(),
+                Some(fields) if fields.first() == Some(&"p") => vec![InputFormat::Dimacs],
+                _ => Vec::new(),
+            }
+        }
+    }
+}
+
+/// The formats a file may be in, most likely first, judged from its first bytes (once
+/// decompressed) and its extension
+pub fn detect_input_formats(path: &str) -> io::Result<Vec<InputFormat>> {
+    let raw_head = read_head(File::open(path)?)?;
+    let mut formats = if raw_head.starts_with(SQLITE_MAGIC) {
+        vec![InputFormat::GeoPackage]
+    } else if raw_head.starts_with(SHAPEFILE_MAGIC) {
+        vec![InputFormat::Shapefile]
+    } else if raw_head
+        .get(4..32)
+        .is_some_and(|header| header.windows(9).any(|w| w == b"OSMHeader"))
+    {
+        vec![InputFormat::OsmPbf]
+    } else {
+        let head = read_head(open_input(path)?)?;
+        sniff_text(&String::from_utf8_lossy(&head))
+    };
+
+    if let Some(format) = contents_path(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(InputFormat::from_extension)
+    {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    Ok(formats)
+}
+
+//Formats whose readers are left out of the build are still detected, so that reading them
+//fails naming the feature they need
+#[cfg(not(all(
+    feature = "csv",
+    feature = "geopackage",
+    feature = "osm-pbf",
+    feature = "shapefile",
+    feature = "xml"
+)))]
+fn disabled(format: InputFormat, feature: &str) -> Error {
+    anyhow!(
+        "Reading {} requires speedicycle's `{}` feature",
+        format,
+        feature
+    )
+}
+
+/// Reads a graph from a file in the given format, with that format's reader options
+pub fn read_graph_as<N, E, Ix>(
+    path: &str,
+    format: InputFormat,
+    options: &InputOptions,
+) -> Result<GraphRepresentation<N, E, Ix>, Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    N: FromStr + TryFrom<u32> + TryFrom<i64> + Eq + Hash + Copy + Debug + PartialOrd,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: FromStr + From<f64> + PartialOrd,
+    Ix: FromStr + Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as FromStr>::Err: Debug,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    Ok(match format {
+        //A sibling `.co` coordinate file is read too, if one exists
+        InputFormat::Dimacs => {
+            let co_path = companion_co_path(path);
+            read_from_dimacs(
+                path,
+                co_path.as_deref().and_then(|co_path| co_path.to_str()),
+                &options.dimacs_co,
+            )?
+        }
+        InputFormat::DimacsGr => {
+            let co_path = companion_co_path(path);
+            read_from_dimacs_gr(
+                path,
+                co_path.as_deref().and_then(|co_path| co_path.to_str()),
+                &options.dimacs_co,
+            )?
+        }
+        InputFormat::EdgesJson => read_from_edges_json_file(path)?.0,
+        InputFormat::GeoJson => read_from_geojson_file(path, &options.geojson)?.0,
+        InputFormat::NodeLink => read_from_node_link_file(path, &options.node_link)?.0,
+        InputFormat::OverpassJson => read_from_overpass_json(path, &options.osm)?.0,
+        #[cfg(feature = "xml")]
+        InputFormat::GraphMl => read_from_graphml(path, &options.graphml)?.0,
+        #[cfg(feature = "xml")]
+        InputFormat::OsmXml => read_from_osm_xml(path, &options.osm)?.0,
+        #[cfg(feature = "osm-pbf")]
+        InputFormat::OsmPbf => read_from_osm_pbf(path, &options.osm)?.0,
+        #[cfg(feature = "geopackage")]
+        InputFormat::GeoPackage => read_from_geopackage(path, &options.geopackage)?.0,
+        #[cfg(feature = "shapefile")]
+        InputFormat::Shapefile => read_from_shapefile(path, &options.shapefile)?.0,
+        #[cfg(feature = "csv")]
+        InputFormat::Csv => read_from_csv(options.csv_nodes_path.as_deref(), path, &options.csv)?.0,
+        #[cfg(not(feature = "xml"))]
+        InputFormat::GraphMl | InputFormat::OsmXml => return Err(disabled(format, "xml")),
+        #[cfg(not(feature = "osm-pbf"))]
+        InputFormat::OsmPbf => return Err(disabled(format, "osm-pbf")),
+        #[cfg(not(feature = "geopackage"))]
+        InputFormat::GeoPackage => return Err(disabled(format, "geopackage")),
+        #[cfg(not(feature = "shapefile"))]
+        InputFormat::Shapefile => return Err(disabled(format, "shapefile")),
+        #[cfg(not(feature = "csv"))]
+        InputFormat::Csv => return Err(disabled(format, "csv")),
+    })
+}
+
+/// Reads a graph from a file in whichever supported format it is in, also returning that
+/// format
+///
+/// Each format `detect_input_formats` finds likely is tried in turn with its reader's
+/// options from `options`. When none of them can read the file, the error lists every format
+/// tried, with the reason each failed.
+pub fn read_graph<N, E, Ix>(
+    path: &str,
+    options: &InputOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, InputFormat), Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    N: FromStr + TryFrom<u32> + TryFrom<i64> + Eq + Hash + Copy + Debug + PartialOrd,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: FromStr + From<f64> + PartialOrd,
+    Ix: FromStr + Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as FromStr>::Err: Debug,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let formats = detect_input_formats(path).with_context(|| format!("Reading {}", path))?;
+    if formats.is_empty() {
+        return Err(anyhow!(
+            "{}: not in any supported graph format; checked for {}",
+            path,
+            InputFormat::ALL
+                .iter()
+                .map(|format| format.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    let mut failures = Vec::new();
+    for format in formats {
+        match read_graph_as(path, format, options) {
+            Ok(gr) => return Ok((gr, format)),
+            Err(e) => failures.push(format!("  {}: {:#}", format, e)),
+        }
+    }
+    Err(anyhow!(
+        "{}: could not be read in any of the formats tried:\n{}",
+        path,
+        failures.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use tempfile::{Builder, NamedTempFile};
+
+    //Ids of the size current OSM node ids have reached, beyond what a u32 can hold
+    const FIRST_ID: u64 = 12_000_000_001;
+
+    fn write_file(suffix: &str, contents: &[u8]) -> NamedTempFile {
+        let mut file = Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(contents).unwrap();
+        file
+    }
+
+    fn format_of(file: &NamedTempFile) -> InputFormat {
+        read_graph::<u64, f64, u32>(file.path().to_str().unwrap(), &InputOptions::default())
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn detects_text_formats_from_their_contents() {
+        #[cfg_attr(not(feature = "xml"), allow(unused_mut))]
+        let mut cases = vec![
+            (
+                "c sample\np 2 1\nv 10\nv 11\ne 0 1 2\n",
+                InputFormat::Dimacs,
+            ),
+            ("p sp 2 2\na 1 2 3\na 2 1 3\n", InputFormat::DimacsGr),
+            (
+                r#"[{"edge_id":7,"start_node":10,"end_node":11,"weight":2.0}]"#,
+                InputFormat::EdgesJson,
+            ),
+            (
+                r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{},
+                "geometry":{"type":"LineString","coordinates":[[-73.0,40.0],[-73.0,40.001]]}}]}"#,
+                InputFormat::GeoJson,
+            ),
+            (
+                r#"{"directed":false,"multigraph":false,"graph":{},"nodes":[{"id":0},{"id":1}],
+                "links":[{"source":0,"target":1,"weight":2.0}]}"#,
+                InputFormat::NodeLink,
+            ),
+            (
+                r#"{"elements":[{"type":"node","id":1,"lat":40.0,"lon":-73.0},
+                {"type":"node","id":2,"lat":40.001,"lon":-73.0},
+                {"type":"way","id":3,"nodes":[1,2],"tags":{"highway":"path"}}]}"#,
+                InputFormat::OverpassJson,
+            ),
+        ];
+        #[cfg(feature = "xml")]
+        cases.extend([
+            (
+                r#"<?xml version="1.0"?><graphml><graph edgedefault="undirected">
+                <node id="0"/><node id="1"/><edge source="0" target="1"/></graph></graphml>"#,
+                InputFormat::GraphMl,
+            ),
+            (
+                r#"<?xml version="1.0"?><osm version="0.6">
+                <node id="1" lat="40.0" lon="-73.0"/><node id="2" lat="40.001" lon="-73.0"/>
+                <way id="3"><nd ref="1"/><nd ref="2"/><tag k="highway" v="path"/></way></osm>"#,
+                InputFormat::OsmXml,
+            ),
+        ]);
+        for (contents, format) in cases {
+            let file = write_file(".txt", contents.as_bytes());
+            assert_eq!(format_of(&file), format, "{}", contents);
+        }
+    }
+
+    #[test]
+    fn detects_binary_formats_and_extensions() {
+        #[cfg(feature = "csv")]
+        {
+            let file = write_file(".csv", b"from,to,length\n1,2,3.5\n");
+            assert_eq!(format_of(&file), InputFormat::Csv);
+        }
+
+        #[cfg(feature = "geopackage")]
+        {
+            let file = NamedTempFile::new().unwrap();
+            let connection = rusqlite::Connection::open(file.path()).unwrap();
+            connection
+                .execute_batch(
+                    r#"CREATE TABLE edges ("from" INTEGER, "to" INTEGER, length REAL);
+                    INSERT INTO edges VALUES (1, 2, 3.5);"#,
+                )
+                .unwrap();
+            drop(connection);
+            assert_eq!(format_of(&file), InputFormat::GeoPackage);
+        }
+
+        let file = write_file(".bin", &osm_pbf());
+        assert_eq!(
+            detect_input_formats(file.path().to_str().unwrap()).unwrap(),
+            vec![InputFormat::OsmPbf]
+        );
+
+        let file = write_file(".json", b"{}");
+        assert_eq!(
+            detect_input_formats(file.path().to_str().unwrap()).unwrap(),
+            vec![InputFormat::EdgesJson]
+        );
+        let file = write_file(".json", br#"{"type": "FeatureCollection", "features": []}"#);
+        assert_eq!(
+            detect_input_formats(file.path().to_str().unwrap()).unwrap(),
+            vec![InputFormat::GeoJson, InputFormat::EdgesJson]
+        );
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn reads_csv_with_the_configured_columns() {
+        let nodes = write_file(".csv", b"node;lat;lon\n1;40.7;-73.9\n2;40.701;-73.9\n");
+        let edges = write_file(".csv", b"a;b;meters\n1;2;111.5\n");
+        let options = InputOptions {
+            csv: CsvOptions {
+                delimiter: b';',
+                node_id: "node".to_string(),
+                edge_from: "a".to_string(),
+                edge_to: "b".to_string(),
+                edge_length: "meters".to_string(),
+                ..Default::default()
+            },
+            csv_nodes_path: Some(nodes.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let (gr, format) =
+            read_graph::<u64, f64, u32>(edges.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(format, InputFormat::Csv);
+        assert_eq!(gr.edge_list, vec![(0, 1, 111.5)]);
+        assert_eq!(gr.coordinates.len(), 2);
+    }
+
+    #[test]
+    fn lists_the_formats_checked_or_tried() {
+        let file = write_file(".txt", b"neither graph nor anything else\n");
+        let message =
+            read_graph::<u64, f64, u32>(file.path().to_str().unwrap(), &InputOptions::default())
+                .unwrap_err()
+                .to_string();
+        assert!(message.contains("not in any supported graph format"));
+        assert!(InputFormat::ALL
+            .iter()
+            .all(|format| message.contains(&format.to_string())));
+
+        let file = write_file(".gr", b"p 2 1\nv 10\nv 11\ne 0 5 2\n");
+        let message = format!(
+            "{:#}",
+            read_graph::<u64, f64, u32>(file.path().to_str().unwrap(), &InputOptions::default())
+                .unwrap_err()
+        );
+        assert!(message.contains("could not be read in any of the formats tried"));
+        assert!(message.contains("  DIMACS: "));
+        assert!(message.contains("  DIMACS shortest path (.gr): "));
+    }
+
+    #[cfg(not(feature = "csv"))]
+    #[test]
+    fn names_the_feature_a_format_left_out_needs() {
+        let file = write_file(".csv", b"from,to,length\n1,2,3.5\n");
+        let message = format!(
+            "{:#}",
+            read_graph::<u64, f64, u32>(file.path().to_str().unwrap(), &InputOptions::default())
+                .unwrap_err()
+        );
+        assert!(message.contains("  CSV: Reading CSV requires speedicycle's `csv` feature"));
+    }
+
+    //Protocol buffer encoding, enough of it to write a small OSM PBF file by hand
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn zigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    fn varint_field(field: u64, value: u64, out: &mut Vec<u8>) {
+        varint(field << 3, out);
+        varint(value, out);
+    }
+
+    fn bytes_field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        varint((field << 3) | 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed_field(field: u64, values: &[u64], out: &mut Vec<u8>) {
+        let mut packed = Vec::new();
+        values.iter().for_each(|value| varint(*value, &mut packed));
+        bytes_field(field, &packed, out);
+    }
+
+    fn blob(kind: &str, data: &[u8], out: &mut Vec<u8>) {
+        let mut blob = Vec::new();
+        bytes_field(1, data, &mut blob);
+        varint_field(2, data.len() as u64, &mut blob);
+        let mut header = Vec::new();
+        bytes_field(1, kind.as_bytes(), &mut header);
+        varint_field(3, blob.len() as u64, &mut header);
+        out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&blob);
+    }
+
+    //Two nodes with ids beyond a u32, 111 m apart, joined by a footpath
+    fn osm_pbf() -> Vec<u8> {
+        let mut header_block = Vec::new();
+        bytes_field(4, b"OsmSchema-V0.6", &mut header_block);
+
+        let mut strings = Vec::new();
+        for s in ["", "highway", "path"] {
+            bytes_field(1, s.as_bytes(), &mut strings);
+        }
+        let mut nodes = Vec::new();
+        //Positions in units of 100 nanodegrees, the default granularity
+        for (id, lat) in [(FIRST_ID, 400_000_000), (FIRST_ID + 1, 400_010_000)] {
+            let mut node = Vec::new();
+            varint_field(1, zigzag(id as i64), &mut node);
+            varint_field(8, zigzag(lat), &mut node);
+            varint_field(9, zigzag(-730_000_000), &mut node);
+            bytes_field(1, &node, &mut nodes);
+        }
+        let mut way = Vec::new();
+        varint_field(1, 3, &mut way);
+        packed_field(2, &[1], &mut way);
+        packed_field(3, &[2], &mut way);
+        //Node references are delta coded
+        packed_field(8, &[zigzag(FIRST_ID as i64), zigzag(1)], &mut way);
+        let mut ways = Vec::new();
+        bytes_field(3, &way, &mut ways);
+
+        let mut primitive_block = Vec::new();
+        bytes_field(1, &strings, &mut primitive_block);
+        bytes_field(2, &nodes, &mut primitive_block);
+        bytes_field(2, &ways, &mut primitive_block);
+
+        let mut file = Vec::new();
+        blob("OSMHeader", &header_block, &mut file);
+        blob("OSMData", &primitive_block, &mut file);
+        file
+    }
+
+    fn osm_files() -> Vec<(InputFormat, NamedTempFile)> {
+        let overpass = format!(
+            r#"{{"elements":[{{"type":"node","id":{a},"lat":40.0,"lon":-73.0}},
+            {{"type":"node","id":{b},"lat":40.001,"lon":-73.0}},
+            {{"type":"way","id":3,"nodes":[{a},{b}],"tags":{{"highway":"path"}}}}]}}"#,
+            a = FIRST_ID,
+            b = FIRST_ID + 1
+        );
+        #[cfg_attr(not(any(feature = "xml", feature = "osm-pbf")), allow(unused_mut))]
+        let mut files = vec![(
+            InputFormat::OverpassJson,
+            write_file(".json", overpass.as_bytes()),
+        )];
+        #[cfg(feature = "xml")]
+        files.push((
+            InputFormat::OsmXml,
+            write_file(
+                ".osm",
+                format!(
+                    r#"<osm version="0.6">
+                    <node id="{a}" lat="40.0" lon="-73.0"/><node id="{b}" lat="40.001" lon="-73.0"/>
+                    <way id="3"><nd ref="{a}"/><nd ref="{b}"/><tag k="highway" v="path"/></way></osm>"#,
+                    a = FIRST_ID,
+                    b = FIRST_ID + 1
+                )
+                .as_bytes(),
+            ),
+        ));
+        #[cfg(feature = "osm-pbf")]
+        files.push((InputFormat::OsmPbf, write_file(".osm.pbf", &osm_pbf())));
+        files
+    }
+
+    #[test]
+    fn reads_real_sized_osm_ids_in_every_format() {
+        for (format, file) in osm_files() {
+            let (gr, found) = read_graph::<u64, f64, u32>(
+                file.path().to_str().unwrap(),
+                &InputOptions::default(),
+            )
+            .unwrap();
+
+            assert_eq!(found, format);
+            assert_eq!(gr.edge_list.len(), 1, "{}", format);
+            let (u, v, w) = gr.edge_list[0];
+            let mut labels = vec![gr.node_map[&u], gr.node_map[&v]];
+            labels.sort();
+            assert_eq!(labels, vec![FIRST_ID, FIRST_ID + 1], "{}", format);
+            assert!((w - 111.2).abs() < 0.5, "{}: {}", format, w);
+        }
+    }
+
+    #[test]
+    fn keeps_osm_ids_as_attributes_for_small_labels() {
+        for (format, file) in osm_files() {
+            let (gr, _) = read_graph::<u32, f64, u32>(
+                file.path().to_str().unwrap(),
+                &InputOptions::default(),
+            )
+            .unwrap();
+
+            let mut osm_ids: Vec<u64> = gr
+                .node_map
+                .keys()
+                .map(|ind| gr.node_attributes[ind]["osm_id"].as_u64().unwrap())
+                .collect();
+            osm_ids.sort();
+            assert_eq!(osm_ids, vec![FIRST_ID, FIRST_ID + 1], "{}", format);
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use super::compression::open_input;
+use super::geometry::{geodesic_length, Coordinate};
+use super::{next_node_index, Attributes, GraphRepresentation};
+
+use anyhow::{anyhow, Context, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+
+/// Key names used in node-link JSON, as produced by NetworkX's `node_link_data`
+#[derive(Debug, Clone)]
+pub struct NodeLinkOptions {
+    pub node_id: String,
+    /// Top-level key holding the edge list; `links` is what `node_link_graph` reads by
+    /// default, and the reader also accepts `edges`, which NetworkX 3.4 can be asked to use
+    pub links: String,
+    pub source: String,
+    pub target: String,
+    pub weight: String,
+    /// Weight of links without a `weight` key whose endpoints lack coordinates; links
+    /// between positioned nodes are given their geodesic length in meters instead
+    pub default_weight: f64,
+    /// Nodes are only given coordinates when both the latitude and longitude keys are present
+    pub latitude: String,
+    pub longitude: String,
+    pub elevation: String,
+}
+
+impl Default for NodeLinkOptions {
+    fn default() -> Self {
+        Self {
+            node_id: "id".to_string(),
+            links: "links".to_string(),
+            source: "source".to_string(),
+            target: "target".to_string(),
+            weight: "weight".to_string(),
+            default_weight: 1.0,
+            latitude: "lat".to_string(),
+            longitude: "lon".to_string(),
+            elevation: "elevation".to_string(),
+        }
+    }
+}
+
+fn take_key<T>(object: &mut Map<String, Value>, key: &str, what: &str) -> Result<T, Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    let val = object
+        .remove(key)
+        .ok_or_else(|| anyhow!("{} has no `{}` key", what, key))?;
+    serde_json::from_value(val.clone())
+        .map_err(|_| anyhow!("{} has an invalid `{}` value {}", what, key, val))
+}
+
+/// Reads a graph from node-link JSON, as written by NetworkX's `node_link_data`
+///
+/// Links are treated as undirected, whatever the document's `directed` flag, and links
+/// without a weight are given their length or `default_weight`, as in GraphML. As in
+/// NetworkX, a link may name a node missing from the node list, which is then added without
+/// attributes. All other node and link keys are kept as node and edge attributes, and the
+/// document's `graph` object is returned alongside.
+#[allow(clippy::type_complexity)]
+pub fn read_from_node_link<N, E, Ix>(
+    json_string: &str,
+    options: &NodeLinkOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Attributes), Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    E: From<f64>,
+    N: Eq + Hash + Copy + Debug,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    node_link_to_graph(serde_json::from_str(json_string)?, options)
+}
+
+/// Reads a graph from a node-link JSON file, which may be gzip or zstd compressed, as
+/// `read_from_node_link` does
+///
+/// The file is parsed as it is read, without first being read into memory whole.
+#[allow(clippy::type_complexity)]
+pub fn read_from_node_link_file<N, E, Ix>(
+    path: &str,
+    options: &NodeLinkOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Attributes), Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    E: From<f64>,
+    N: Eq + Hash + Copy + Debug,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let document = open_input(path)
+        .map_err(Error::from)
+        .and_then(|reader| Ok(serde_json::from_reader(reader)?))
+        .with_context(|| format!("Reading {}", path))?;
+    node_link_to_graph(document, options)
+}
+
+#[allow(clippy::type_complexity)]
+fn node_link_to_graph<N, E, Ix>(
+    document: Value,
+    options: &NodeLinkOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>, Attributes), Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    E: From<f64>,
+    N: Eq + Hash + Copy + Debug,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut document = match document {
+        Value::Object(document) => document,
+        _ => return Err(anyhow!("Expected a node-link JSON object")),
+    };
+    let nodes = match document.remove("nodes") {
+        Some(Value::Array(nodes)) => nodes,
+        _ => return Err(anyhow!("Node-link document has no `nodes` array")),
+    };
+    let links = match document
+        .remove(&options.links)
+        .or_else(|| document.remove("links"))
+        .or_else(|| document.remove("edges"))
+    {
+        Some(Value::Array(links)) => links,
+        _ => {
+            return Err(anyhow!(
+                "Node-link document has no `{}` array",
+                options.links
+            ))
+        }
+    };
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+    let graph_attributes = match document.remove("graph") {
+        Some(Value::Object(graph)) => graph,
+        _ => Map::new(),
+    };
+    let mut node_attributes = HashMap::<Ix, Attributes>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+
+    for (i, node) in nodes.into_iter().enumerate() {
+        let mut node = match node {
+            Value::Object(node) => node,
+            _ => return Err(anyhow!("Node {} is not an object", i)),
+        };
+        let node_id = take_key::<N>(&mut node, &options.node_id, &format!("Node {}", i))?;
+        if node_weight_to_index.contains_key(&node_id) {
+            return Err(anyhow!("Duplicate node id {:?}", node_id));
+        }
+        let ind = next_node_index(&node_map)?;
+
+        let position = |key: &str| node.get(key).and_then(|val| val.as_f64());
+        if let (Some(lat), Some(lon)) = (position(&options.latitude), position(&options.longitude))
+        {
+            let mut coord = Coordinate::new(lon, lat);
+            if let Some(elevation) = position(&options.elevation) {
+                coord = coord.with_elevation(elevation);
+                node.remove(&options.elevation);
+            }
+            coordinates.insert(ind, coord);
+            node.remove(&options.latitude);
+            node.remove(&options.longitude);
+        }
+
+        node_map.insert(ind, node_id);
+        node_weight_to_index.insert(node_id, ind);
+        node_attributes.insert(ind, node);
+    }
+
+    let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+    for (i, link) in links.into_iter().enumerate() {
+        let mut link = match link {
+            Value::Object(link) => link,
+            _ => return Err(anyhow!("Link {} is not an object", i)),
+        };
+        let what = format!("Link {}", i);
+        let mut index_of = |node_id: N| -> Result<Ix, Error> {
+            if let Some(ind) = node_weight_to_index.get(&node_id) {
+                return Ok(*ind);
+            }
+            let ind = next_node_index(&node_map)?;
+            node_map.insert(ind, node_id);
+            node_weight_to_index.insert(node_id, ind);
+            node_attributes.insert(ind, Map::new());
+            Ok(ind)
+        };
+        let u = index_of(take_key::<N>(&mut link, &options.source, &what)?)?;
+        let v = index_of(take_key::<N>(&mut link, &options.target, &what)?)?;
+        let w = if link.contains_key(&options.weight) {
+            take_key::<E>(&mut link, &options.weight, &what)?
+        } else {
+            E::from(match (coordinates.get(&u), coordinates.get(&v)) {
+                (Some(from), Some(to)) => geodesic_length(&[*from, *to]),
+                _ => options.default_weight,
+            })
+        };
+
+        edge_list.push((u, v, w));
+        edge_attributes.push(link);
+    }
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(node_attributes, edge_attributes),
+        node_weight_to_index,
+        graph_attributes,
+    ))
+}
+
+/// Renders a graph as node-link JSON that NetworkX's `node_link_graph` can load
+///
+/// Nodes are written in index order. The graph's node and edge attributes are merged into
+/// the node and link objects alongside the keys that describe the graph itself, and
+/// `graph_attributes`, when given, become the document's `graph` object.
+pub fn graph_to_node_link<N, E, Ix>(
+    gr: &GraphRepresentation<N, E, Ix>,
+    graph_attributes: Option<&Attributes>,
+    options: &NodeLinkOptions,
+) -> Result<Value, Error>
+where
+    N: Serialize + Eq + Hash,
+    E: Serialize,
+    Ix: Ord + Hash + Debug,
+{
+    let mut indices: Vec<&Ix> = gr.node_map.keys().collect();
+    indices.sort();
+
+    let nodes = indices
+        .into_iter()
+        .map(|ind| {
+            let node_id = &gr.node_map[ind];
+            let mut node = gr.node_attributes.get(ind).cloned().unwrap_or_default();
+            node.insert(options.node_id.clone(), serde_json::to_value(node_id)?);
+            if let Some(coord) = gr.coordinates.get(ind) {
+                node.insert(options.latitude.clone(), json!(coord.lat));
+                node.insert(options.longitude.clone(), json!(coord.lon));
+                if let Some(elevation) = coord.elevation {
+                    node.insert(options.elevation.clone(), json!(elevation));
+                }
+            }
+            Ok(Value::Object(node))
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+
+    let mut seen_pairs = HashSet::new();
+    let mut multigraph = false;
+    let links = gr
+        .edge_list
+        .iter()
+        .enumerate()
+        .map(|(i, (u, v, w))| {
+            let node_id = |ind: &Ix| {
+                gr.node_map
+                    .get(ind)
+                    .ok_or_else(|| anyhow!("Edge refers to unknown node index {:?}", ind))
+            };
+            let (source, target) = (node_id(u)?, node_id(v)?);
+            multigraph |= !seen_pairs.insert(if u <= v { (u, v) } else { (v, u) });
+
+            let mut link = gr.edge_attributes.get(i).cloned().unwrap_or_default();
+            link.insert(options.source.clone(), serde_json::to_value(source)?);
+            link.insert(options.target.clone(), serde_json::to_value(target)?);
+            link.insert(options.weight.clone(), serde_json::to_value(w)?);
+            Ok(Value::Object(link))
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+
+    let mut document = Map::new();
+    document.insert("directed".to_string(), json!(false));
+    document.insert("multigraph".to_string(), json!(multigraph));
+    document.insert(
+        "graph".to_string(),
+        Value::Object(graph_attributes.cloned().unwrap_or_default()),
+    );
+    document.insert("nodes".to_string(), Value::Array(nodes));
+    document.insert(options.links.clone(), Value::Array(links));
+    Ok(Value::Object(document))
+}
+
+pub fn write_graph_to_node_link<N, E, Ix>(
+    path: &str,
+    gr: &GraphRepresentation<N, E, Ix>,
+    graph_attributes: Option<&Attributes>,
+    options: &NodeLinkOptions,
+) -> Result<(), Error>
+where
+    N: Serialize + Eq + Hash,
+    E: Serialize,
+    Ix: Ord + Hash + Debug,
+{
+    fs::write(
+        path,
+        serde_json::to_string(&graph_to_node_link(gr, graph_attributes, options)?)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(json_string: &str) -> GraphRepresentation<u32, f64, u32> {
+        read_from_node_link(json_string, &NodeLinkOptions::default())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn writes_links_by_default() {
+        let gr = read(
+            r#"{"nodes": [{"id": 4, "lat": 40.7, "lon": -73.9}, {"id": 9}],
+                "links": [{"source": 4, "target": 9, "weight": 2.5, "name": "Main"}]}"#,
+        );
+        let document = graph_to_node_link(&gr, None, &NodeLinkOptions::default()).unwrap();
+
+        assert!(document.get("edges").is_none());
+        assert_eq!(document["links"][0]["name"], "Main");
+        assert_eq!(document["links"][0]["weight"], 2.5);
+        assert_eq!(document["nodes"][0]["lat"], 40.7);
+        assert_eq!(document["multigraph"], false);
+    }
+
+    #[test]
+    fn reads_links_and_edges_keys() {
+        for key in ["links", "edges"] {
+            let gr = read(&format!(
+                r#"{{"nodes": [{{"id": 4}}], "{}": [{{"source": 4, "target": 9, "weight": 1}}]}}"#,
+                key
+            ));
+
+            assert_eq!(gr.node_map, HashMap::from([(0, 4), (1, 9)]));
+            assert_eq!(gr.edge_list, vec![(0, 1, 1.0)]);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_writer() {
+        let gr = read(
+            r#"{"nodes": [{"id": 1, "kind": "a"}, {"id": 2}, {"id": 3}],
+                "links": [{"source": 1, "target": 2, "weight": 1},
+                          {"source": 2, "target": 3, "weight": 2},
+                          {"source": 3, "target": 2, "weight": 2}]}"#,
+        );
+        let document = graph_to_node_link(&gr, None, &NodeLinkOptions::default()).unwrap();
+        let reread = read(&document.to_string());
+
+        assert_eq!(document["multigraph"], true);
+        assert_eq!(reread.node_map, gr.node_map);
+        assert_eq!(reread.edge_list, gr.edge_list);
+        assert_eq!(reread.node_attributes[&0]["kind"], "a");
+    }
+
+    #[test]
+    fn falls_back_to_geodesic_length_then_the_default_weight() {
+        let options = NodeLinkOptions {
+            default_weight: 3.0,
+            ..Default::default()
+        };
+        let (gr, _, _) = read_from_node_link::<u32, f64, u32>(
+            r#"{"nodes": [{"id": 1, "lat": 40.7, "lon": -73.9}, {"id": 2, "lat": 40.701, "lon": -73.9}],
+                "links": [{"source": 1, "target": 2},
+                          {"source": 2, "target": 3},
+                          {"source": 3, "target": 1, "weight": 7.5}]}"#,
+            &options,
+        )
+        .unwrap();
+        let weights: Vec<f64> = gr.edge_list.iter().map(|(_, _, w)| *w).collect();
+
+        assert!((weights[0] - 111.2).abs() < 0.5, "{}", weights[0]);
+        assert_eq!(weights[1], 3.0);
+        assert_eq!(weights[2], 7.5);
+    }
+
+    #[test]
+    fn rejects_more_nodes_than_the_index_type_holds() {
+        let links: Vec<String> = (0..150)
+            .map(|i| format!(r#"{{"source": {}, "target": {}}}"#, 2 * i, 2 * i + 1))
+            .collect();
+        let result = read_from_node_link::<u32, f64, u8>(
+            &format!(r#"{{"nodes": [], "links": [{}]}}"#, links.join(",")),
+            &NodeLinkOptions::default(),
+        );
+
+        assert!(result.unwrap_err().to_string().contains("more nodes"));
+    }
+}
@@ -0,0 +1,632 @@
+use super::compression::open_input;
+use super::geometry::Coordinate;
+use super::{Attributes, GraphRepresentation};
+
+use anyhow::{anyhow, Error};
+#[cfg(feature = "osm-pbf")]
+use osmpbf::{Element, ElementReader};
+#[cfg(feature = "xml")]
+use quick_xml::events::{BytesStart, Event};
+#[cfg(feature = "xml")]
+use quick_xml::{Reader, XmlVersion};
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(feature = "osm-pbf")]
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// Decides which OSM ways are kept when building a graph, based on their tags
+#[derive(Debug, Clone)]
+pub struct OsmTagFilter {
+    /// A way is kept if any of these rules match; `(key, None)` matches any value of `key`
+    pub include: Vec<(String, Option<String>)>,
+    /// A way is dropped if any of these rules match, even when an include rule does
+    pub exclude: Vec<(String, Option<String>)>,
+}
+
+impl OsmTagFilter {
+    /// Keeps every `highway` that can be travelled on foot
+    pub fn walkable() -> Self {
+        let rule = |k: &str, v: Option<&str>| (k.to_string(), v.map(|v| v.to_string()));
+        Self {
+            include: vec![rule("highway", None)],
+            exclude: vec![
+                rule("highway", Some("motorway")),
+                rule("highway", Some("motorway_link")),
+                rule("highway", Some("trunk")),
+                rule("highway", Some("trunk_link")),
+                rule("highway", Some("construction")),
+                rule("highway", Some("proposed")),
+                rule("highway", Some("abandoned")),
+                rule("area", Some("yes")),
+                rule("foot", Some("no")),
+                rule("access", Some("no")),
+                rule("access", Some("private")),
+            ],
+        }
+    }
+
+    pub fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        let rule_matches = |(key, value): &(String, Option<String>)| match (tags.get(key), value) {
+            (Some(_), None) => true,
+            (Some(tag_value), Some(value)) => tag_value == value,
+            (None, _) => false,
+        };
+
+        self.include.iter().any(rule_matches) && !self.exclude.iter().any(rule_matches)
+    }
+}
+
+impl Default for OsmTagFilter {
+    fn default() -> Self {
+        Self::walkable()
+    }
+}
+
+//Node positions and the node lists and tags of the ways that passed the tag filter,
+//as collected from any of the OSM input formats
+#[derive(Default)]
+pub(crate) struct OsmNetwork {
+    pub nodes: HashMap<i64, Coordinate>,
+    pub ways: Vec<(Vec<i64>, HashMap<String, String>)>,
+}
+
+impl OsmNetwork {
+    //Ways are split wherever they meet another way (or themselves), so that graph
+    //nodes are the intersections and dead ends of the network. Each resulting segment
+    //becomes an edge weighted by its haversine length in meters, with the tags of its
+    //way as attributes. Nodes missing from the extract (e.g. ways clipped at its
+    //boundary) end the segment early.
+    //Nodes are labelled by their OSM ids when every id fits the label type. Otherwise
+    //they are labelled by index, and keep their OSM id as an `osm_id` attribute.
+    #[allow(clippy::type_complexity)]
+    pub fn into_graph<N, E, Ix>(
+        self,
+    ) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+    where
+        N: Eq + Hash + Copy + TryFrom<i64>,
+        E: From<f64>,
+        Ix: Eq + Hash + Copy + TryFrom<u32>,
+        <Ix as TryFrom<u32>>::Error: Debug,
+    {
+        let mut ref_counts = HashMap::<i64, u32>::new();
+        self.ways.iter().for_each(|(way, _)| {
+            way.iter()
+                .for_each(|r| *ref_counts.entry(*r).or_default() += 1);
+            //Count way endpoints twice so that they are always split points
+            if let (Some(first), Some(last)) = (way.first(), way.last()) {
+                *ref_counts.entry(*first).or_default() += 1;
+                *ref_counts.entry(*last).or_default() += 1;
+                //A closed way would otherwise become a single self-loop, so split it halfway round
+                if first == last && way.len() > 2 {
+                    *ref_counts.entry(way[way.len() / 2]).or_default() += 1;
+                }
+            }
+        });
+        let is_split = |r: &i64| ref_counts.get(r).is_some_and(|count| *count > 1);
+
+        let mut edges = Vec::<(i64, i64, f64, usize)>::new();
+        for (way_index, (way, _)) in self.ways.iter().enumerate() {
+            let mut segment: Option<(i64, f64)> = None;
+            let mut last: Option<(i64, Coordinate)> = None;
+
+            for r in way {
+                let coord = match self.nodes.get(r) {
+                    Some(coord) => coord,
+                    None => {
+                        if let (Some((start, length)), Some((end, _))) = (segment, last) {
+                            if start != end {
+                                edges.push((start, end, length, way_index));
+                            }
+                        }
+                        segment = None;
+                        last = None;
+                        continue;
+                    }
+                };
+
+                segment = match (segment, last) {
+                    (Some((start, length)), Some((_, prev))) => {
+                        let length = length + prev.haversine_distance(coord);
+                        if is_split(r) {
+                            if start != *r {
+                                edges.push((start, *r, length, way_index));
+                            }
+                            Some((*r, 0.0))
+                        } else {
+                            Some((start, length))
+                        }
+                    }
+                    _ => Some((*r, 0.0)),
+                };
+                last = Some((*r, *coord));
+            }
+        }
+
+        let mut osm_ids = Vec::<i64>::new();
+        let mut osm_to_index = HashMap::<i64, Ix>::new();
+        let mut edge_list = Vec::<(Ix, Ix, E)>::new();
+        let mut edge_attributes = Vec::<Attributes>::new();
+        let way_attributes: Vec<Attributes> = self
+            .ways
+            .iter()
+            .map(|(_, tags)| {
+                tags.iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().into()))
+                    .collect()
+            })
+            .collect();
+
+        for (u, v, w, way_index) in edges {
+            let mut index_of = |osm_id: i64| -> Result<Ix, Error> {
+                if let Some(ind) = osm_to_index.get(&osm_id) {
+                    return Ok(*ind);
+                }
+                let ind = u32::try_from(osm_ids.len())
+                    .ok()
+                    .and_then(|len| Ix::try_from(len).ok())
+                    .ok_or_else(|| {
+                        anyhow!("The network has more nodes than the node index type can hold")
+                    })?;
+                osm_ids.push(osm_id);
+                osm_to_index.insert(osm_id, ind);
+                Ok(ind)
+            };
+            edge_list.push((index_of(u)?, index_of(v)?, E::from(w)));
+            edge_attributes.push(way_attributes[way_index].clone());
+        }
+
+        let id_labels: Option<Vec<N>> = osm_ids.iter().map(|id| N::try_from(*id).ok()).collect();
+        let mut node_map = HashMap::<Ix, N>::new();
+        let mut node_weight_to_index = HashMap::<N, Ix>::new();
+        let mut coordinates = HashMap::<Ix, Coordinate>::new();
+        let mut node_attributes = HashMap::<Ix, Attributes>::new();
+        for (i, osm_id) in osm_ids.iter().enumerate() {
+            let ind = osm_to_index[osm_id];
+            let node_id = match &id_labels {
+                Some(labels) => labels[i],
+                None => {
+                    node_attributes.insert(
+                        ind,
+                        Attributes::from_iter([("osm_id".into(), (*osm_id).into())]),
+                    );
+                    N::try_from(i as i64).map_err(|_| {
+                        anyhow!(
+                            "{} nodes cannot be labelled by the node label type",
+                            osm_ids.len()
+                        )
+                    })?
+                }
+            };
+            node_map.insert(ind, node_id);
+            node_weight_to_index.insert(node_id, ind);
+            coordinates.insert(ind, self.nodes[osm_id]);
+        }
+
+        Ok((
+            GraphRepresentation::new(node_map, edge_list)
+                .with_coordinates(coordinates)
+                .with_attributes(node_attributes, edge_attributes),
+            node_weight_to_index,
+        ))
+    }
+}
+
+#[cfg(feature = "xml")]
+fn attribute(e: &BytesStart, key: &str) -> Result<Option<String>, Error> {
+    for attr in e.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == key {
+            return Ok(Some(
+                attr.normalized_value(XmlVersion::Implicit1_0)?.into_owned(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "xml")]
+fn parsed_attribute<T: std::str::FromStr>(e: &BytesStart, key: &str) -> Result<T, Error> {
+    attribute(e, key)?
+        .ok_or_else(|| anyhow!("<{}> element is missing `{}`", e.name().as_ref(), key))?
+        .parse::<T>()
+        .map_err(|_| anyhow!("Invalid `{}` attribute", key))
+}
+
+/// Reads a walkable network from an OSM XML (`.osm`) file, with nodes labelled by OSM node id
+/// when the label type can hold every id, and by index otherwise
+#[cfg(feature = "xml")]
+#[allow(clippy::type_complexity)]
+pub fn read_from_osm_xml<N, E, Ix>(
+    filepath: &str,
+    filter: &OsmTagFilter,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<i64>,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut reader = Reader::from_reader(open_input(filepath)?);
+    let mut buf = Vec::new();
+    let mut network = OsmNetwork::default();
+    let mut current_way: Option<(Vec<i64>, HashMap<String, String>)> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                "node" => {
+                    let id = parsed_attribute::<i64>(&e, "id")?;
+                    let lat = parsed_attribute::<f64>(&e, "lat")?;
+                    let lon = parsed_attribute::<f64>(&e, "lon")?;
+                    network.nodes.insert(id, Coordinate::new(lon, lat));
+                }
+                "way" => {
+                    current_way = Some((Vec::new(), HashMap::new()));
+                }
+                "nd" => {
+                    if let Some((refs, _)) = current_way.as_mut() {
+                        refs.push(parsed_attribute::<i64>(&e, "ref")?);
+                    }
+                }
+                "tag" => {
+                    if let Some((_, tags)) = current_way.as_mut() {
+                        if let (Some(k), Some(v)) = (attribute(&e, "k")?, attribute(&e, "v")?) {
+                            tags.insert(k, v);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::End(e) if e.name().as_ref() == "way" => {
+                if let Some((refs, tags)) = current_way.take() {
+                    if refs.len() > 1 && filter.matches(&tags) {
+                        network.ways.push((refs, tags));
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    network.into_graph()
+}
+
+/// Reads a walkable network from an OSM PBF (`.osm.pbf`) extract, with nodes labelled as for
+/// `read_from_osm_xml`
+///
+/// The file is streamed block by block, twice: once to collect the ways that pass
+/// `filter`, and once to collect the positions of only the nodes those ways use.
+#[cfg(feature = "osm-pbf")]
+#[allow(clippy::type_complexity)]
+pub fn read_from_osm_pbf<N, E, Ix>(
+    filepath: &str,
+    filter: &OsmTagFilter,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<i64>,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let mut network = OsmNetwork::default();
+
+    ElementReader::from_path(filepath)?.for_each(|element| {
+        if let Element::Way(way) = element {
+            let tags: HashMap<String, String> = way
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            if filter.matches(&tags) {
+                let refs: Vec<i64> = way.refs().collect();
+                if refs.len() > 1 {
+                    network.ways.push((refs, tags));
+                }
+            }
+        }
+    })?;
+
+    let used_nodes: HashSet<i64> = network
+        .ways
+        .iter()
+        .flat_map(|(refs, _)| refs)
+        .copied()
+        .collect();
+    ElementReader::from_path(filepath)?.for_each(|element| {
+        let (id, coord) = match element {
+            Element::Node(node) => (node.id(), Coordinate::new(node.lon(), node.lat())),
+            Element::DenseNode(node) => (node.id(), Coordinate::new(node.lon(), node.lat())),
+            _ => return,
+        };
+        if used_nodes.contains(&id) {
+            network.nodes.insert(id, coord);
+        }
+    })?;
+
+    network.into_graph()
+}
+
+#[derive(Deserialize)]
+struct OverpassResponse {
+    elements: Vec<OverpassElement>,
+}
+
+#[derive(Deserialize)]
+struct OverpassPoint {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum OverpassElement {
+    Node {
+        id: i64,
+        lat: Option<f64>,
+        lon: Option<f64>,
+    },
+    Way {
+        nodes: Vec<i64>,
+        tags: Option<HashMap<String, String>>,
+        //Positions of `nodes`, as given by `out geom`; null for nodes clipped by a bounding box
+        #[serde(default)]
+        geometry: Vec<Option<OverpassPoint>>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Reads a walkable network from a saved Overpass API JSON response, with nodes labelled as
+/// for `read_from_osm_xml`
+///
+/// Node positions are taken from node elements and from the geometry of ways output with
+/// `out geom`, so both `out body geom` and `out skel` (with its nodes recursed) responses can
+/// be read. Ways with tags must pass `filter`; ways without any, as output by `out skel`,
+/// are taken to have been selected by the query and are always kept.
+#[allow(clippy::type_complexity)]
+pub fn read_from_overpass_json<N, E, Ix>(
+    filepath: &str,
+    filter: &OsmTagFilter,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<i64>,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    let response: OverpassResponse = serde_json::from_reader(open_input(filepath)?)
+        .map_err(|e| anyhow!("{} is not an Overpass JSON response: {}", filepath, e))?;
+    let mut network = OsmNetwork::default();
+
+    for element in response.elements {
+        match element {
+            OverpassElement::Node {
+                id,
+                lat: Some(lat),
+                lon: Some(lon),
+            } => {
+                network.nodes.insert(id, Coordinate::new(lon, lat));
+            }
+            OverpassElement::Way {
+                nodes,
+                tags,
+                geometry,
+            } => {
+                nodes
+                    .iter()
+                    .zip(&geometry)
+                    .filter_map(|(id, point)| point.as_ref().map(|point| (id, point)))
+                    .for_each(|(id, point)| {
+                        network
+                            .nodes
+                            .insert(*id, Coordinate::new(point.lon, point.lat));
+                    });
+                let tags = tags.unwrap_or_default();
+                if nodes.len() > 1 && (tags.is_empty() || filter.matches(&tags)) {
+                    network.ways.push((nodes, tags));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    network.into_graph()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use tempfile::NamedTempFile;
+
+    //Ids of the size current OSM node ids have reached, beyond what a u32 can hold
+    const FIRST_ID: i64 = 12_000_000_001;
+
+    fn crossing() -> OsmNetwork {
+        let mut network = OsmNetwork::default();
+        for i in 0..5 {
+            let offset = i as f64 * 0.001;
+            network
+                .nodes
+                .insert(FIRST_ID + i, Coordinate::new(-73.0 + offset, 40.0 + offset));
+        }
+        let tags = HashMap::from([("highway".to_string(), "residential".to_string())]);
+        let way = |ids: &[i64]| (ids.iter().map(|i| FIRST_ID + i).collect(), tags.clone());
+        network.ways = vec![way(&[0, 1, 2]), way(&[3, 1, 4])];
+        network
+    }
+
+    #[test]
+    fn labels_nodes_by_osm_id_when_they_fit() {
+        let (gr, node_weight_to_index) = crossing().into_graph::<u64, f64, u32>().unwrap();
+
+        assert_eq!(gr.edge_list.len(), 4);
+        let centre = node_weight_to_index[&(FIRST_ID as u64 + 1)];
+        assert_eq!(gr.node_map[&centre], FIRST_ID as u64 + 1);
+        assert!(gr.node_attributes.is_empty());
+    }
+
+    #[test]
+    fn labels_nodes_by_index_when_osm_ids_do_not_fit() {
+        let (gr, node_weight_to_index) = crossing().into_graph::<u32, f64, u32>().unwrap();
+
+        assert_eq!(gr.edge_list.len(), 4);
+        assert_eq!(node_weight_to_index.len(), 5);
+        let mut osm_ids: Vec<i64> = gr
+            .node_map
+            .iter()
+            .map(|(ind, label)| {
+                assert_eq!(*ind, *label);
+                gr.node_attributes[ind]["osm_id"].as_i64().unwrap()
+            })
+            .collect();
+        osm_ids.sort();
+        assert_eq!(osm_ids, (0..5).map(|i| FIRST_ID + i).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn reads_real_sized_ids_from_osm_xml() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"<osm version="0.6">
+            <node id="{a}" lat="40.0" lon="-73.0"/>
+            <node id="{b}" lat="40.001" lon="-73.0"/>
+            <way id="1"><nd ref="{a}"/><nd ref="{b}"/><tag k="highway" v="path"/></way>
+            </osm>"#,
+            a = FIRST_ID,
+            b = FIRST_ID + 1
+        )
+        .unwrap();
+        let (gr, node_weight_to_index) = read_from_osm_xml::<u64, f64, u32>(
+            file.path().to_str().unwrap(),
+            &OsmTagFilter::default(),
+        )
+        .unwrap();
+
+        assert_eq!(gr.edge_list.len(), 1);
+        assert!(node_weight_to_index.contains_key(&(FIRST_ID as u64 + 1)));
+    }
+
+    fn network(nodes: &[(i64, f64)], ways: &[&[i64]]) -> OsmNetwork {
+        OsmNetwork {
+            nodes: nodes
+                .iter()
+                .map(|(i, lat)| (FIRST_ID + i, Coordinate::new(-73.0, *lat)))
+                .collect(),
+            ways: ways
+                .iter()
+                .map(|way| (way.iter().map(|i| FIRST_ID + i).collect(), HashMap::new()))
+                .collect(),
+        }
+    }
+
+    fn edges_by_osm_id(gr: &GraphRepresentation<u64, f64, u32>) -> Vec<(i64, i64, f64)> {
+        let osm_id = |ind: &u32| gr.node_map[ind] as i64 - FIRST_ID;
+        gr.edge_list
+            .iter()
+            .map(|(u, v, w)| (osm_id(u), osm_id(v), (w / 111.195).round()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_ways_only_where_they_meet_or_end() {
+        //Nodes a thousandth of a degree (about 111 m) apart along a meridian; the second way
+        //joins the first at node 2, and node 1 is a bend within the first
+        let nodes: Vec<(i64, f64)> = (0..7).map(|i| (i, 40.0 + i as f64 * 0.001)).collect();
+        let (gr, _) = network(&nodes, &[&[0, 1, 2, 3], &[5, 2, 6]])
+            .into_graph::<u64, f64, u32>()
+            .unwrap();
+
+        assert_eq!(
+            edges_by_osm_id(&gr),
+            vec![(0, 2, 2.0), (2, 3, 1.0), (5, 2, 3.0), (2, 6, 4.0)]
+        );
+        assert!(!gr
+            .node_map
+            .values()
+            .any(|label| *label as i64 == FIRST_ID + 1));
+    }
+
+    #[test]
+    fn splits_closed_ways_and_ends_segments_at_missing_nodes() {
+        let nodes: Vec<(i64, f64)> = [0, 1, 2, 3, 5, 6]
+            .iter()
+            .map(|i| (*i, 40.0 + *i as f64 * 0.001))
+            .collect();
+        let (gr, _) = network(&nodes, &[&[0, 1, 2, 3, 0], &[5, 4, 6]])
+            .into_graph::<u64, f64, u32>()
+            .unwrap();
+
+        //The loop is split halfway round, at node 2; node 4 lies outside the extract, so the
+        //second way leaves no segment long enough to keep
+        assert_eq!(edges_by_osm_id(&gr), vec![(0, 2, 2.0), (2, 0, 4.0)]);
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn keeps_walkable_ways_and_drops_the_rest() {
+        let filter = OsmTagFilter::default();
+        let tags = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert!(filter.matches(&tags(&[("highway", "footway")])));
+        assert!(filter.matches(&tags(&[("highway", "residential"), ("name", "Main")])));
+        assert!(!filter.matches(&tags(&[("highway", "motorway")])));
+        assert!(!filter.matches(&tags(&[("highway", "service"), ("access", "private")])));
+        assert!(!filter.matches(&tags(&[("building", "yes")])));
+        assert!(!filter.matches(&tags(&[])));
+
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"<osm version="0.6">
+            <node id="1" lat="40.0" lon="-73.0"/>
+            <node id="2" lat="40.001" lon="-73.0"/>
+            <node id="3" lat="40.002" lon="-73.0"/>
+            <way id="10"><nd ref="1"/><nd ref="2"/><tag k="highway" v="footway"/></way>
+            <way id="11"><nd ref="2"/><nd ref="3"/><tag k="highway" v="motorway"/></way>
+            <way id="12"><nd ref="3"/><nd ref="1"/></way>
+            </osm>"#
+        )
+        .unwrap();
+        let (gr, _) =
+            read_from_osm_xml::<u64, f64, u32>(file.path().to_str().unwrap(), &filter).unwrap();
+
+        assert_eq!(gr.edge_list.len(), 1);
+        assert_eq!(gr.edge_attributes[0]["highway"], "footway");
+    }
+
+    #[test]
+    fn keeps_untagged_overpass_skeleton_ways() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"elements": [
+                {{"type": "node", "id": 1, "lat": 40.0, "lon": -73.0}},
+                {{"type": "node", "id": 2, "lat": 40.001, "lon": -73.0}},
+                {{"type": "node", "id": 3, "lat": 40.002, "lon": -73.0}},
+                {{"type": "way", "id": 10, "nodes": [1, 2]}},
+                {{"type": "way", "id": 11, "nodes": [2, 3], "tags": {{"highway": "motorway"}}}}
+            ]}}"#
+        )
+        .unwrap();
+        let (gr, node_weight_to_index) = read_from_overpass_json::<u64, f64, u32>(
+            file.path().to_str().unwrap(),
+            &OsmTagFilter::default(),
+        )
+        .unwrap();
+
+        assert_eq!(gr.edge_list.len(), 1);
+        assert!(gr.edge_attributes[0].is_empty());
+        assert!(!node_weight_to_index.contains_key(&3));
+    }
+}
@@ -0,0 +1,168 @@
+use super::geometry::{circuit_positions, Coordinate, EdgeGeometries};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::{anyhow, Error};
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+use std::fs;
+use std::hash::Hash;
+
+#[derive(Debug, Clone)]
+pub struct PolylineOptions {
+    /// Decimal places kept of each latitude and longitude; 5 as used by Google, or 6 as used
+    /// by OSRM and Valhalla
+    pub precision: u32,
+}
+
+impl Default for PolylineOptions {
+    fn default() -> Self {
+        Self { precision: 5 }
+    }
+}
+
+//Appends a signed value in the polyline encoding: zigzagged, then written five bits at
+//a time from the least significant, each chunk offset into printable ASCII
+fn encode_value(out: &mut String, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x20 {
+        out.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+        value >>= 5;
+    }
+    out.push(char::from(value as u8 + 63));
+}
+
+/// Encodes positions as a Google encoded polyline, in latitude, longitude order; elevations
+/// are dropped
+pub fn encode_polyline(positions: &[Coordinate], precision: u32) -> Result<String, Error> {
+    if !(1..=7).contains(&precision) {
+        return Err(anyhow!("Unsupported polyline precision {}", precision));
+    }
+    let factor = 10f64.powi(precision as i32);
+    let mut out = String::new();
+    let mut previous = (0, 0);
+    for c in positions {
+        let point = (
+            (c.lat * factor).round() as i64,
+            (c.lon * factor).round() as i64,
+        );
+        encode_value(&mut out, point.0 - previous.0);
+        encode_value(&mut out, point.1 - previous.1);
+        previous = point;
+    }
+    Ok(out)
+}
+
+/// Renders circuits as tab-separated `circuit` and `polyline` columns, one row per
+/// `(name, circuit)` pair, following the shapes in `edge_geometries` where given
+pub fn circuits_to_polyline<G>(
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &PolylineOptions,
+) -> Result<String, Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    let mut out = String::from("circuit\tpolyline\n");
+    for (name, circuit) in circuits {
+        let positions = circuit_positions(
+            &circuit.ordered_node_weight_list,
+            &circuit.edge_list,
+            coordinates,
+            edge_geometries,
+        )?;
+        let _ = writeln!(
+            out,
+            "{}\t{}",
+            name,
+            encode_polyline(&positions, options.precision)?
+        );
+    }
+    Ok(out)
+}
+
+pub fn write_circuits_to_polyline<G>(
+    path: &str,
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+    options: &PolylineOptions,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    fs::write(
+        path,
+        circuits_to_polyline(circuits, coordinates, edge_geometries, options)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //The inverse of `encode_polyline`, to check values survive the trip
+    fn decode_polyline(polyline: &str, precision: u32) -> Vec<(f64, f64)> {
+        let factor = 10f64.powi(precision as i32);
+        let mut values = Vec::new();
+        let (mut value, mut shift) = (0u64, 0);
+        for byte in polyline.bytes() {
+            let chunk = u64::from(byte - 63);
+            value |= (chunk & 0x1f) << shift;
+            shift += 5;
+            if chunk < 0x20 {
+                values.push(((value >> 1) as i64) ^ -((value & 1) as i64));
+                (value, shift) = (0, 0);
+            }
+        }
+        let mut previous = (0, 0);
+        values
+            .chunks(2)
+            .map(|pair| {
+                previous = (previous.0 + pair[0], previous.1 + pair[1]);
+                (previous.0 as f64 / factor, previous.1 as f64 / factor)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_googles_example() {
+        let positions = [
+            Coordinate::new(-120.2, 38.5),
+            Coordinate::new(-120.95, 40.7),
+            Coordinate::new(-126.453, 43.252),
+        ];
+        assert_eq!(
+            encode_polyline(&positions, 5).unwrap(),
+            "_p~iF~ps|U_ulLnnqC_mqNvxq`@"
+        );
+    }
+
+    #[test]
+    fn keeps_six_decimal_places() {
+        let positions = [
+            Coordinate::new(-73.985_743, 40.748_412),
+            Coordinate::new(-73.985_1, 40.749_003).with_elevation(12.0),
+            Coordinate::new(-73.985_743, 40.748_412),
+        ];
+        let decoded = decode_polyline(&encode_polyline(&positions, 6).unwrap(), 6);
+
+        assert_eq!(decoded.len(), 3);
+        for (c, (lat, lon)) in positions.iter().zip(decoded) {
+            assert!((c.lat - lat).abs() < 1e-9 && (c.lon - lon).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_precisions() {
+        let positions = [Coordinate::new(0.0, 0.0)];
+        assert!(encode_polyline(&positions, 0).is_err());
+        assert!(encode_polyline(&positions, 8).is_err());
+        assert_eq!(encode_polyline(&[], 5).unwrap(), "");
+    }
+}
@@ -0,0 +1,378 @@
+use super::geometry::{geodesic_length, Coordinate, CoordinateSnapper, EdgeGeometries};
+use super::{Attributes, GraphRepresentation};
+
+use anyhow::{anyhow, Context, Error};
+use serde_json::Value;
+use shapefile::dbase::{FieldValue, Record};
+use shapefile::{Reader, Shape, ShapeReader};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Controls how a Shapefile street network is turned into a graph
+#[derive(Debug, Clone)]
+pub struct ShapefileOptions {
+    /// Endpoints closer than this distance (in coordinate units, i.e. degrees) share a node
+    pub snap_tolerance: f64,
+    /// DBF field holding each edge's weight; geodesic length in meters is used when `None`
+    pub weight_field: Option<String>,
+}
+
+impl Default for ShapefileOptions {
+    fn default() -> Self {
+        Self {
+            snap_tolerance: 1e-7,
+            weight_field: None,
+        }
+    }
+}
+
+fn field_to_value(field: FieldValue) -> Value {
+    match field {
+        FieldValue::Character(s) => s.map_or(Value::Null, Value::from),
+        FieldValue::Memo(s) => s.into(),
+        FieldValue::Numeric(n) => n.map_or(Value::Null, Value::from),
+        FieldValue::Float(f) => f.map_or(Value::Null, |f| Value::from(f as f64)),
+        FieldValue::Logical(b) => b.map_or(Value::Null, Value::from),
+        FieldValue::Integer(i) => i.into(),
+        FieldValue::Currency(c) | FieldValue::Double(c) => c.into(),
+        FieldValue::Date(d) => d.map_or(Value::Null, |d| {
+            format!("{:04}-{:02}-{:02}", d.year(), d.month(), d.day()).into()
+        }),
+        FieldValue::DateTime(dt) => {
+            let (d, t) = (dt.date(), dt.time());
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                d.year(),
+                d.month(),
+                d.day(),
+                t.hours(),
+                t.minutes(),
+                t.seconds()
+            )
+            .into()
+        }
+    }
+}
+
+//Polyline parts, as coordinates; positions are taken to be longitude and latitude
+fn polyline_parts(shape: Shape) -> Result<Vec<Vec<Coordinate>>, Error> {
+    Ok(match shape {
+        Shape::NullShape => Vec::new(),
+        Shape::Polyline(line) => line
+            .parts()
+            .iter()
+            .map(|part| part.iter().map(|p| Coordinate::new(p.x, p.y)).collect())
+            .collect(),
+        Shape::PolylineM(line) => line
+            .parts()
+            .iter()
+            .map(|part| part.iter().map(|p| Coordinate::new(p.x, p.y)).collect())
+            .collect(),
+        Shape::PolylineZ(line) => line
+            .parts()
+            .iter()
+            .map(|part| {
+                part.iter()
+                    .map(|p| Coordinate::new(p.x, p.y).with_elevation(p.z))
+                    .collect()
+            })
+            .collect(),
+        other => {
+            return Err(anyhow!(
+                "Expected polyline shapes, not {}",
+                other.shapetype()
+            ))
+        }
+    })
+}
+
+//The coordinate system named by the `.prj` file next to the `.shp` file, if there is one;
+//positions are read as longitude and latitude, so only geographic systems are accepted
+fn check_projection(path: &str) -> Result<(), Error> {
+    let prj_path = Path::new(path).with_extension("prj");
+    let wkt = match fs::read_to_string(&prj_path) {
+        Ok(wkt) => wkt,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(Error::from(e).context(format!("Reading {}", prj_path.display()))),
+    };
+    let wkt = wkt.trim_start_matches('\u{feff}').trim();
+    let keyword = wkt.split(['[', '(']).next().unwrap_or_default().trim();
+    if ["GEOGCS", "GEOGCRS", "GEOGRAPHICCRS"].contains(&keyword.to_ascii_uppercase().as_str()) {
+        return Ok(());
+    }
+    let name = wkt.split('"').nth(1).unwrap_or(keyword);
+    Err(anyhow!(
+        "{}: positions are in the non-geographic coordinate system `{}`, not longitude and \
+        latitude; reproject it to WGS 84 (EPSG:4326) first",
+        path,
+        name
+    ))
+}
+
+//Each polyline part becomes a single edge between its first and last points, as with
+//the LineStrings of GeoJSON input; intermediate points contribute to its length, and
+//are kept as the edge's geometry.
+//The `.dbf` file next to the `.shp` file supplies weights and edge attributes, and may
+//be left out when weights are computed. Null shapes are skipped, as are parts whose
+//endpoints snap to the same node. Records are read one at a time rather than all up front.
+#[allow(clippy::type_complexity)]
+pub fn read_from_shapefile<N, E, Ix>(
+    path: &str,
+    options: &ShapefileOptions,
+) -> Result<(GraphRepresentation<N, E, Ix>, HashMap<N, Ix>), Error>
+where
+    N: Eq + Hash + Copy + TryFrom<u32>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: From<f64>,
+    Ix: Eq + Hash + Copy + TryFrom<u32>,
+    <Ix as TryFrom<u32>>::Error: Debug,
+{
+    check_projection(path)?;
+    let has_dbf = Path::new(path).with_extension("dbf").exists();
+    if !has_dbf {
+        if let Some(field) = &options.weight_field {
+            return Err(anyhow!(
+                "{}: no .dbf file to read the `{}` field from",
+                path,
+                field
+            ));
+        }
+    }
+
+    let mut snapper = CoordinateSnapper::new(options.snap_tolerance);
+    let mut edges = Vec::<(u32, u32, f64)>::new();
+    let mut edge_attributes = Vec::<Attributes>::new();
+    let mut edge_geometries = EdgeGeometries::new();
+
+    let mut add_record = |i: usize, shape: Shape, record: Option<Record>| -> Result<(), Error> {
+        let parts = polyline_parts(shape).with_context(|| format!("{}: record {}", path, i))?;
+        let lengths: Vec<f64> = parts.iter().map(|part| geodesic_length(part)).collect();
+        let total_length: f64 = lengths.iter().sum();
+
+        let mut properties: Attributes = record
+            .map(|record| {
+                record
+                    .into_iter()
+                    .map(|(name, field)| (name, field_to_value(field)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let field_weight = match &options.weight_field {
+            Some(field) => Some(
+                properties
+                    .remove(field)
+                    .and_then(|val| val.as_f64())
+                    .ok_or_else(|| {
+                        anyhow!("{}: record {} has no numeric `{}` field", path, i, field)
+                    })?,
+            ),
+            None => None,
+        };
+
+        for (part, length) in parts.iter().zip(lengths) {
+            if let (Some(first), Some(last)) = (part.first(), part.last()) {
+                let u = snapper.snap(*first);
+                let v = snapper.snap(*last);
+                if u == v {
+                    continue;
+                }
+
+                //As with GeoJSON, a field weight describes the whole record, so it is
+                //shared among its parts in proportion to their length
+                let w = match field_weight {
+                    Some(w) if parts.len() > 1 && total_length > 0.0 => w * length / total_length,
+                    Some(w) => w,
+                    None => length,
+                };
+                edge_geometries.insert(edges.len(), part.clone());
+                edges.push((u, v, w));
+                edge_attributes.push(properties.clone());
+            }
+        }
+        Ok(())
+    };
+
+    if has_dbf {
+        let mut reader = Reader::from_path(path).with_context(|| format!("Reading {}", path))?;
+        for (i, shape_and_record) in reader.iter_shapes_and_records().enumerate() {
+            let (shape, record) = shape_and_record.with_context(|| format!("Reading {}", path))?;
+            add_record(i, shape, Some(record))?;
+        }
+    } else {
+        let mut reader =
+            ShapeReader::from_path(path).with_context(|| format!("Reading {}", path))?;
+        for (i, shape) in reader.iter_shapes().enumerate() {
+            add_record(i, shape.with_context(|| format!("Reading {}", path))?, None)?;
+        }
+    }
+
+    let mut node_map = HashMap::<Ix, N>::new();
+    let mut node_weight_to_index = HashMap::<N, Ix>::new();
+    let mut coordinates = HashMap::<Ix, Coordinate>::new();
+
+    let mut node_indices = Vec::<Ix>::with_capacity(snapper.coordinates.len());
+    for (i, coord) in (0..).zip(snapper.coordinates) {
+        let too_many = || anyhow!("The network has more nodes than the node index type can hold");
+        let ind = Ix::try_from(i).map_err(|_| too_many())?;
+        let node_id = N::try_from(i).map_err(|_| too_many())?;
+        node_map.insert(ind, node_id);
+        node_weight_to_index.insert(node_id, ind);
+        coordinates.insert(ind, coord);
+        node_indices.push(ind);
+    }
+
+    let edge_list = edges
+        .into_iter()
+        .map(|(u, v, w)| {
+            (
+                node_indices[u as usize],
+                node_indices[v as usize],
+                E::from(w),
+            )
+        })
+        .collect();
+
+    //Records without a .dbf file carry no attributes
+    if !has_dbf {
+        edge_attributes.clear();
+    }
+
+    Ok((
+        GraphRepresentation::new(node_map, edge_list)
+            .with_coordinates(coordinates)
+            .with_attributes(HashMap::new(), edge_attributes)
+            .with_edge_geometries(edge_geometries),
+        node_weight_to_index,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shapefile::dbase::{FieldName, TableWriterBuilder};
+    use shapefile::{Point, Polyline, ShapeWriter, Writer};
+    use tempfile::TempDir;
+
+    fn polyline(parts: &[&[(f64, f64)]]) -> Polyline {
+        Polyline::with_parts(
+            parts
+                .iter()
+                .map(|part| part.iter().map(|(x, y)| Point::new(*x, *y)).collect())
+                .collect(),
+        )
+    }
+
+    //Main street runs north from the origin, and a two-part record runs east and then back
+    //round to within snapping distance of the origin
+    fn streets() -> [Polyline; 2] {
+        [
+            polyline(&[&[(0.0, 0.0), (0.0, 0.001)]]),
+            polyline(&[
+                &[(0.0, 0.001), (0.001, 0.001)],
+                &[(0.001, 0.001), (0.001, 0.0), (1e-8, 0.0)],
+            ]),
+        ]
+    }
+
+    fn write_with_dbf(dir: &TempDir) -> String {
+        let path = dir.path().join("streets.shp");
+        let field = |name: &str| FieldName::try_from(name).unwrap();
+        let table = TableWriterBuilder::new()
+            .add_character_field(field("name"), 20)
+            .add_numeric_field(field("length"), 10, 2);
+        let mut writer = Writer::from_path(&path, table).unwrap();
+        for (shape, (name, length)) in streets().iter().zip([(Some("Main"), 120.0), (None, 80.0)]) {
+            let mut record = Record::default();
+            record.insert(
+                "name".to_string(),
+                FieldValue::Character(name.map(str::to_string)),
+            );
+            record.insert("length".to_string(), FieldValue::Numeric(Some(length)));
+            writer.write_shape_and_record(shape, &record).unwrap();
+        }
+        path.to_str().unwrap().to_string()
+    }
+
+    fn write_without_dbf(dir: &TempDir) -> String {
+        let path = dir.path().join("streets.shp");
+        let mut writer = ShapeWriter::from_path(&path).unwrap();
+        for shape in streets() {
+            writer.write_shape(&shape).unwrap();
+        }
+        path.to_str().unwrap().to_string()
+    }
+
+    fn weighted() -> ShapefileOptions {
+        ShapefileOptions {
+            weight_field: Some("length".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn snaps_endpoints_and_shares_field_weights_among_parts() {
+        let dir = TempDir::new().unwrap();
+        let path = write_with_dbf(&dir);
+        let (gr, _) = read_from_shapefile::<u32, f64, u32>(&path, &weighted()).unwrap();
+
+        assert_eq!(gr.node_map.len(), 3);
+        assert_eq!(gr.edge_list.len(), 3);
+        assert_eq!(gr.edge_list[2].1, gr.edge_list[0].0);
+        assert_eq!(gr.edge_list[0].2, 120.0);
+        //The second part covers two sides of the square to the first part's one
+        assert!((gr.edge_list[1].2 + gr.edge_list[2].2 - 80.0).abs() < 1e-9);
+        assert!(gr.edge_list[2].2 > 1.9 * gr.edge_list[1].2);
+        assert_eq!(gr.edge_attributes[0]["name"], "Main");
+        assert!(gr.edge_attributes[0].get("length").is_none());
+        assert_eq!(gr.edge_attributes[1]["name"], Value::Null);
+        assert_eq!(gr.edge_geometries[&2].len(), 3);
+    }
+
+    #[test]
+    fn measures_edges_without_a_dbf_file() {
+        let dir = TempDir::new().unwrap();
+        let path = write_without_dbf(&dir);
+        let (gr, _) =
+            read_from_shapefile::<u32, f64, u32>(&path, &ShapefileOptions::default()).unwrap();
+
+        assert_eq!(gr.edge_list.len(), 3);
+        assert!(
+            (gr.edge_list[0].2 - 111.2).abs() < 0.5,
+            "{}",
+            gr.edge_list[0].2
+        );
+        assert!(gr.edge_attributes.is_empty());
+
+        let err = read_from_shapefile::<u32, f64, u32>(&path, &weighted()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("no .dbf file to read the `length` field from"));
+    }
+
+    #[test]
+    fn accepts_geographic_and_rejects_projected_coordinates() {
+        let dir = TempDir::new().unwrap();
+        let path = write_without_dbf(&dir);
+        let prj_path = dir.path().join("streets.prj");
+
+        fs::write(
+            &prj_path,
+            r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#,
+        )
+        .unwrap();
+        assert!(read_from_shapefile::<u32, f64, u32>(&path, &ShapefileOptions::default()).is_ok());
+
+        fs::write(
+            &prj_path,
+            r#"PROJCS["WGS_1984_UTM_Zone_18N",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Transverse_Mercator"],UNIT["Meter",1.0]]"#,
+        )
+        .unwrap();
+        let err =
+            read_from_shapefile::<u32, f64, u32>(&path, &ShapefileOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("`WGS_1984_UTM_Zone_18N`"));
+    }
+}
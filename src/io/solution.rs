@@ -0,0 +1,300 @@
+use crate::graph::euler::{EulerCircuit, WalkedEdge};
+
+use anyhow::{anyhow, Context, Error};
+use petgraph::stable_graph::{IndexType, StableDiGraph};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::ops::Sub;
+
+//Value of the `format` key identifying a solution document
+const SOLUTION_FORMAT: &str = "speedicycle-solution";
+/// Bumped whenever the layout of the solution document changes
+pub const SOLUTION_VERSION: u32 = 1;
+
+/// The fixed parameters of the circuit search, and how much of it a solution took
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolverParameters {
+    /// Fraction of the target length beyond which nodes are trimmed from the graph
+    pub trim_factor: f64,
+    pub max_iterations: u32,
+    /// Number of double path searches run before valid circuits were found
+    pub iterations: u32,
+}
+
+/// A circuit, with edges identified by their position in the input edge list unless the input
+/// gave them ids of their own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitSolution<N, E, K = usize> {
+    pub name: String,
+    /// Labels of the visited nodes in circuit order, starting and ending at the source
+    pub nodes: Vec<N>,
+    /// The input edges walked, in circuit order
+    pub edges: Vec<WalkedEdge<K>>,
+    pub length: Option<E>,
+    /// Circuit length minus the target length
+    pub deviation: Option<E>,
+}
+
+impl<N, E, K> CircuitSolution<N, E, K> {
+    /// A circuit known only by the labels of the nodes it visits
+    pub fn from_nodes(name: &str, nodes: Vec<N>) -> Self {
+        Self {
+            name: name.to_string(),
+            nodes,
+            edges: Vec::new(),
+            length: None,
+            deviation: None,
+        }
+    }
+
+    /// The same circuit, with each edge identified by `f(id)` instead
+    pub fn map_edge_ids<T>(self, mut f: impl FnMut(K) -> T) -> CircuitSolution<N, E, T> {
+        CircuitSolution {
+            name: self.name,
+            nodes: self.nodes,
+            edges: self
+                .edges
+                .into_iter()
+                .map(|edge| edge.map_id(&mut f))
+                .collect(),
+            length: self.length,
+            deviation: self.deviation,
+        }
+    }
+}
+
+impl<N, E> CircuitSolution<N, E> {
+    /// Describes a circuit found on a graph built by `make_graph`
+    pub fn from_circuit<Ix>(
+        name: &str,
+        circuit: &EulerCircuit<StableDiGraph<N, E, Ix>>,
+        target_length: E,
+    ) -> Self
+    where
+        N: Clone,
+        E: Copy + Sub<Output = E>,
+        Ix: IndexType,
+    {
+        Self {
+            name: name.to_string(),
+            nodes: circuit.ordered_node_weight_list.clone(),
+            edges: circuit.edge_list.clone(),
+            length: Some(circuit.length),
+            deviation: Some(circuit.length - target_length),
+        }
+    }
+}
+
+/// A set of circuits along with the inputs they were found from
+///
+/// Documents loaded from the legacy bare-array format have a `version` of 0, circuits named
+/// `upper` and `lower`, and none of the optional fields set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolutionDocument<N, E, K = usize> {
+    pub version: u32,
+    pub input_path: Option<String>,
+    /// Label of the node the circuits start and end at
+    pub source: Option<N>,
+    pub target_length: Option<E>,
+    /// Distance from the source beyond which the graph was trimmed before searching
+    pub trim_radius: Option<E>,
+    pub solver: Option<SolverParameters>,
+    pub circuits: Vec<CircuitSolution<N, E, K>>,
+}
+
+impl<N, E, K> SolutionDocument<N, E, K> {
+    pub fn new(circuits: Vec<CircuitSolution<N, E, K>>) -> Self {
+        Self {
+            version: SOLUTION_VERSION,
+            input_path: None,
+            source: None,
+            target_length: None,
+            trim_radius: None,
+            solver: None,
+            circuits,
+        }
+    }
+
+    pub fn circuit(&self, name: &str) -> Option<&CircuitSolution<N, E, K>> {
+        self.circuits.iter().find(|circuit| circuit.name == name)
+    }
+}
+
+#[derive(Serialize)]
+struct TaggedDocument<'a, N, E, K> {
+    format: &'a str,
+    #[serde(flatten)]
+    document: &'a SolutionDocument<N, E, K>,
+}
+
+pub fn solutions_to_json<N, E, K>(document: &SolutionDocument<N, E, K>) -> Result<String, Error>
+where
+    N: Serialize,
+    E: Serialize,
+    K: Serialize,
+{
+    Ok(serde_json::to_string(&TaggedDocument {
+        format: SOLUTION_FORMAT,
+        document,
+    })?)
+}
+
+pub fn write_solutions<N, E, K>(
+    path: &str,
+    document: &SolutionDocument<N, E, K>,
+) -> Result<(), Error>
+where
+    N: Serialize,
+    E: Serialize,
+    K: Serialize,
+{
+    fs::write(path, solutions_to_json(document)?)?;
+    Ok(())
+}
+
+/// Reads a solution document, or a legacy solution file holding a bare JSON array of
+/// circuits (upper first, then lower) given as arrays of node labels
+pub fn read_solutions<N, E, K>(path: &str) -> Result<SolutionDocument<N, E, K>, Error>
+where
+    for<'de> N: Deserialize<'de>,
+    for<'de> E: Deserialize<'de>,
+    for<'de> K: Deserialize<'de>,
+{
+    let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path))?;
+    let value: Value =
+        serde_json::from_str(&contents).with_context(|| format!("Parsing {}", path))?;
+
+    if value.is_array() {
+        let circuits: Vec<Vec<N>> = serde_json::from_value(value)
+            .with_context(|| format!("{}: invalid legacy solution file", path))?;
+        let mut document = SolutionDocument::new(
+            circuits
+                .into_iter()
+                .enumerate()
+                .map(|(i, nodes)| {
+                    let name = match i {
+                        0 => "upper".to_string(),
+                        1 => "lower".to_string(),
+                        _ => format!("circuit {}", i),
+                    };
+                    CircuitSolution::from_nodes(&name, nodes)
+                })
+                .collect(),
+        );
+        document.version = 0;
+        return Ok(document);
+    }
+
+    if value.get("format").and_then(Value::as_str) != Some(SOLUTION_FORMAT) {
+        return Err(anyhow!("{} is not a speedicycle solution file", path));
+    }
+    match value.get("version").and_then(Value::as_u64) {
+        Some(version) if (1..=SOLUTION_VERSION as u64).contains(&version) => {}
+        version => {
+            return Err(anyhow!(
+                "{}: unsupported solution version {}",
+                path,
+                version.map_or("(missing)".to_string(), |v| v.to_string())
+            ))
+        }
+    }
+    serde_json::from_value(value).with_context(|| format!("{}: invalid solution document", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn circuit() -> CircuitSolution<u64, f64> {
+        CircuitSolution {
+            name: "upper".to_string(),
+            nodes: vec![7, 8, 7],
+            edges: vec![
+                WalkedEdge {
+                    id: 0,
+                    forward: true,
+                },
+                WalkedEdge {
+                    id: 1,
+                    forward: false,
+                },
+            ],
+            length: Some(5.0),
+            deviation: Some(-1.0),
+        }
+    }
+
+    #[test]
+    fn round_trips_walked_edges() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sols.json");
+        let path = path.to_str().unwrap();
+        let mut document = SolutionDocument::new(vec![circuit()]);
+        document.source = Some(7);
+        document.target_length = Some(6.0);
+        write_solutions(path, &document).unwrap();
+
+        let reread = read_solutions::<u64, f64, usize>(path).unwrap();
+        assert_eq!(reread, document);
+        assert_eq!(reread.version, SOLUTION_VERSION);
+    }
+
+    #[test]
+    fn keeps_caller_edge_ids() {
+        let ids = ["a", "b"];
+        let named = circuit().map_edge_ids(|id| ids[id].to_string());
+        assert_eq!(
+            named.edges,
+            vec![
+                WalkedEdge {
+                    id: "a".to_string(),
+                    forward: true
+                },
+                WalkedEdge {
+                    id: "b".to_string(),
+                    forward: false
+                },
+            ]
+        );
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sols.json");
+        let path = path.to_str().unwrap();
+        let document = SolutionDocument::new(vec![named]);
+        write_solutions(path, &document).unwrap();
+        assert_eq!(read_solutions::<u64, f64, String>(path).unwrap(), document);
+    }
+
+    #[test]
+    fn names_extra_legacy_circuits_by_position() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sols.txt");
+        fs::write(&path, "[[1,2,1],[3,4,3],[5,6,5]]").unwrap();
+
+        let document = read_solutions::<u64, f64, usize>(path.to_str().unwrap()).unwrap();
+        assert_eq!(document.circuits[2].name, "circuit 2");
+        assert_eq!(document.circuit("lower").unwrap().nodes, vec![3, 4, 3]);
+    }
+
+    #[test]
+    fn rejects_other_documents_and_versions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sols.json");
+        let read = |contents: &str| {
+            fs::write(&path, contents).unwrap();
+            read_solutions::<u64, f64, usize>(path.to_str().unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(read(r#"{"type":"FeatureCollection"}"#).contains("not a speedicycle solution"));
+        assert!(
+            read(r#"{"format":"speedicycle-solution","version":99,"circuits":[]}"#)
+                .contains("unsupported solution version 99")
+        );
+        assert!(read(r#"{"format":"speedicycle-solution","circuits":[]}"#)
+            .contains("unsupported solution version (missing)"));
+    }
+}
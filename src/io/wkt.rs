@@ -0,0 +1,188 @@
+use super::geometry::{circuit_positions, Coordinate, EdgeGeometries};
+use crate::graph::euler::EulerCircuit;
+
+use anyhow::{anyhow, Error};
+use petgraph::data::DataMap;
+use petgraph::visit::{Data, GraphBase};
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+use std::fs;
+use std::hash::Hash;
+
+/// Parses a WKT `LINESTRING`, with or without Z values, ignoring any EWKT `SRID=...;` prefix
+pub fn linestring_from_wkt(wkt: &str) -> Result<Vec<Coordinate>, Error> {
+    let invalid = || anyhow!("Invalid WKT LINESTRING `{}`", wkt);
+    let text = wkt.split_once(';').map_or(wkt, |(_srid, rest)| rest).trim();
+    let body = text
+        .get(..10)
+        .filter(|tag| tag.eq_ignore_ascii_case("LINESTRING"))
+        .map(|_| text[10..].trim_start())
+        .ok_or_else(invalid)?;
+    //Measures are not positions, so only the Z variant is accepted
+    let body = match body.get(..1) {
+        Some("Z" | "z") => body[1..].trim_start(),
+        _ => body,
+    };
+    if body.eq_ignore_ascii_case("EMPTY") {
+        return Ok(Vec::new());
+    }
+
+    body.strip_prefix('(')
+        .and_then(|body| body.strip_suffix(')'))
+        .ok_or_else(invalid)?
+        .split(',')
+        .map(|point| {
+            let values = point
+                .split_whitespace()
+                .map(|val| val.parse::<f64>().map_err(|_| invalid()))
+                .collect::<Result<Vec<f64>, Error>>()?;
+            match values.as_slice() {
+                [lon, lat] => Ok(Coordinate::new(*lon, *lat)),
+                [lon, lat, elevation] => Ok(Coordinate::new(*lon, *lat).with_elevation(*elevation)),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
+
+/// Renders positions as a WKT `LINESTRING`, in longitude, latitude order, with Z values
+/// when every position has an elevation
+pub fn linestring_to_wkt(positions: &[Coordinate]) -> String {
+    if positions.is_empty() {
+        return "LINESTRING EMPTY".to_string();
+    }
+    let with_z = positions.iter().all(|c| c.elevation.is_some());
+    let points: Vec<String> = positions
+        .iter()
+        .map(|c| match c.elevation {
+            Some(elevation) if with_z => format!("{} {} {}", c.lon, c.lat, elevation),
+            _ => format!("{} {}", c.lon, c.lat),
+        })
+        .collect();
+    format!(
+        "LINESTRING{} ({})",
+        if with_z { " Z" } else { "" },
+        points.join(", ")
+    )
+}
+
+/// Renders circuits as tab-separated `circuit` and `wkt` columns, one row per
+/// `(name, circuit)` pair, following the shapes in `edge_geometries` where given
+pub fn circuits_to_wkt<G>(
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+) -> Result<String, Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    let mut out = String::from("circuit\twkt\n");
+    for (name, circuit) in circuits {
+        let positions = circuit_positions(
+            &circuit.ordered_node_weight_list,
+            &circuit.edge_list,
+            coordinates,
+            edge_geometries,
+        )?;
+        let _ = writeln!(out, "{}\t{}", name, linestring_to_wkt(&positions));
+    }
+    Ok(out)
+}
+
+pub fn write_circuits_to_wkt<G>(
+    path: &str,
+    circuits: &[(&str, &EulerCircuit<G>)],
+    coordinates: &HashMap<G::NodeWeight, Coordinate>,
+    edge_geometries: &EdgeGeometries,
+) -> Result<(), Error>
+where
+    G: GraphBase + Data + DataMap,
+    G::NodeWeight: Eq + Hash + Debug,
+{
+    fs::write(
+        path,
+        circuits_to_wkt(circuits, coordinates, edge_geometries)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::euler::WalkedEdge;
+    use petgraph::stable_graph::StableDiGraph;
+
+    #[test]
+    fn round_trips_linestrings() {
+        let flat = vec![Coordinate::new(-73.5, 40.25), Coordinate::new(-73.0, 40.0)];
+        let wkt = linestring_to_wkt(&flat);
+        assert_eq!(wkt, "LINESTRING (-73.5 40.25, -73 40)");
+        assert_eq!(linestring_from_wkt(&wkt).unwrap(), flat);
+
+        let raised: Vec<Coordinate> = flat.iter().map(|c| c.with_elevation(3.5)).collect();
+        let wkt = linestring_to_wkt(&raised);
+        assert_eq!(wkt, "LINESTRING Z (-73.5 40.25 3.5, -73 40 3.5)");
+        assert_eq!(linestring_from_wkt(&wkt).unwrap(), raised);
+
+        assert_eq!(linestring_to_wkt(&[]), "LINESTRING EMPTY");
+        assert!(linestring_from_wkt("LINESTRING EMPTY").unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_z_values_unless_every_position_has_one() {
+        let mixed = [
+            Coordinate::new(1.0, 2.0).with_elevation(3.0),
+            Coordinate::new(4.0, 5.0),
+        ];
+        assert_eq!(linestring_to_wkt(&mixed), "LINESTRING (1 2, 4 5)");
+    }
+
+    #[test]
+    fn parses_ewkt_and_rejects_other_geometries() {
+        let parsed = linestring_from_wkt("SRID=4326;linestring(1 2,3 4)").unwrap();
+        assert_eq!(
+            parsed,
+            vec![Coordinate::new(1.0, 2.0), Coordinate::new(3.0, 4.0)]
+        );
+        assert!(linestring_from_wkt("POINT (1 2)").is_err());
+        assert!(linestring_from_wkt("LINESTRING M (1 2 3)").is_err());
+        assert!(linestring_from_wkt("LINESTRING (1 x)").is_err());
+    }
+
+    #[test]
+    fn follows_edge_shapes_in_the_direction_walked() {
+        let circuit = EulerCircuit::<StableDiGraph<u32, f64, u32>> {
+            length: 2.0,
+            node_pair_list: Vec::new(),
+            edge_list: vec![
+                WalkedEdge {
+                    id: 0,
+                    forward: true,
+                },
+                WalkedEdge {
+                    id: 0,
+                    forward: false,
+                },
+            ],
+            ordered_node_weight_list: vec![1, 2, 1],
+        };
+        let coordinates = HashMap::from([
+            (1, Coordinate::new(0.0, 0.0)),
+            (2, Coordinate::new(2.0, 0.0)),
+        ]);
+        let geometries = EdgeGeometries::from([(
+            0,
+            vec![
+                Coordinate::new(0.0, 0.0),
+                Coordinate::new(1.0, 1.0),
+                Coordinate::new(2.0, 0.0),
+            ],
+        )]);
+
+        assert_eq!(
+            circuits_to_wkt(&[("upper", &circuit)], &coordinates, &geometries).unwrap(),
+            "circuit\twkt\nupper\tLINESTRING (0 0, 1 1, 2 0, 1 1, 0 0)\n"
+        );
+    }
+}
@@ -4,43 +4,241 @@ pub mod io;
 extern crate futures;
 extern crate futures_util;
 
-use futures::future::{self, ok, BoxFuture};
-use futures::task::Poll;
-use futures::Future;
-use futures_util::FutureExt;
 use serde::{Deserialize, Serialize};
-use std::boxed;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
+use std::fs;
 use std::hash::Hash;
+use std::io::{self as std_io, Write};
 use std::iter::Sum;
-use std::ops::{Add, AddAssign, Div, Mul, Neg, RemAssign};
-use std::process::Output;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, RemAssign, Sub};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{anyhow, Error};
-use clap::Parser;
+use anyhow::{anyhow, Context, Error};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use geojson::GeoJson;
 use graph::{
-    double_path::double_path, euler::make_euler_circuit, make_graph, trim_graph_at_max_distance,
+    double_path::double_path,
+    euler::{make_euler_circuit, EulerCircuit, WalkedEdge},
+    make_graph, trim_graph_at_max_distance,
+    verify::{node_label_index, verify_circuit, verify_solutions},
 };
-use io::{read_from_dimacs, read_from_edges_json, write_solution_strings_to_file};
+use io::{
+    circuit_to_fit, circuits_to_geojson, circuits_to_gpx, circuits_to_polyline, circuits_to_wkt,
+    graph_to_dot, graph_to_graphml, load_graph_cache, read_from_edges_json, read_graph,
+    read_solutions, save_graph_cache, solutions_to_json, Attributes, CircuitSolution, DotOptions,
+    FitOptions, GpxKind, GpxOptions, GraphMlOptions, InputOptions, PolylineOptions, PreparedGraph,
+    SolutionDocument, SolverParameters,
+};
+#[cfg(feature = "csv")]
+use io::{circuits_to_csv, CsvOptions};
 use num::Bounded;
 use petgraph::algo::{dijkstra, FloatMeasure, Measure};
 use petgraph::stable_graph::{IndexType, StableDiGraph, StableGraph};
 use petgraph::Directed;
+use tempfile::NamedTempFile;
+
+//Nodes further from the source than this fraction of the target length are never searched
+const TRIM_FACTOR: f64 = 0.6;
+const MAX_DOUBLE_PATH_ITERATIONS: i32 = 50;
+//Errors for a search that finds no circuits, or cannot run at all
+const NO_CIRCUITS_FOUND: &str =
+    "Found no circuits on both sides of the target length around the source";
+const SEARCH_FAILED: &str = "Unable to search for circuits around the source";
+//Input and output paths standing for standard input and standard output
+const STDIO_PATH: &str = "-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// JSON solution document recording the circuits and the search that found them
+    Json,
+    /// GeoJSON FeatureCollection; requires vertex coordinates
+    Geojson,
+    /// GPX 1.1 document; requires vertex coordinates
+    Gpx,
+    /// One FIT course file per circuit; requires vertex coordinates
+    Fit,
+    /// CSV table with one row per visited node, including coordinates when known
+    #[cfg(feature = "csv")]
+    Csv,
+    /// GraphML document of the input graph, with the circuits marked as edge attributes
+    Graphml,
+    /// Graphviz DOT rendering of the trimmed graph, with the circuits highlighted
+    Dot,
+    /// Google encoded polyline of each circuit; requires vertex coordinates
+    Polyline,
+    /// WKT LINESTRING of each circuit; requires vertex coordinates
+    Wkt,
+}
+
+impl OutputFormat {
+    //Appended to the input path, less its extension, to name the output file by default
+    fn default_suffix(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "_sols.txt",
+            OutputFormat::Geojson => "_sols.geojson",
+            OutputFormat::Gpx => "_sols.gpx",
+            OutputFormat::Fit => "_sols.fit",
+            #[cfg(feature = "csv")]
+            OutputFormat::Csv => "_sols.csv",
+            OutputFormat::Graphml => "_sols.graphml",
+            OutputFormat::Dot => "_sols.dot",
+            OutputFormat::Polyline => "_sols_polyline.tsv",
+            OutputFormat::Wkt => "_sols_wkt.tsv",
+        }
+    }
+}
+
+/// Finds circuits by default, or runs one of the subcommands
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub route: Option<CLIArgs>,
+}
 
 #[derive(Parser)]
 pub struct CLIArgs {
-    #[arg(short, long)]
+    /// Graph file to read, or `-` for standard input
+    //clap leaves the group it checks to tell whether `Cli::route` was given empty when the
+    //arguments flatten others in turn, so the input path, which routing requires, joins it
+    #[arg(short, long, group = "CLIArgs")]
     input_path: String,
+    /// Label of the node circuits start and end at, as given in the input
     #[arg(short, long)]
-    source_vertex: u32,
+    source_vertex: String,
     #[arg(short, long)]
     target_length: f64,
+    #[arg(short = 'f', long, value_enum, default_value_t = OutputFormat::Json)]
+    output_format: OutputFormat,
+    /// File to write the circuits to, or `-` for standard output; defaults to a file named
+    /// after the input, or to standard output when the input is read from standard input
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Write GPX circuits as tracks rather than routes
+    #[arg(long)]
+    gpx_track: bool,
+    /// Add start and finish waypoints to GPX output
+    #[arg(long)]
+    gpx_waypoints: bool,
+    /// Export only the part of the graph within reach of the source to GraphML
+    #[arg(long)]
+    graphml_trimmed: bool,
+    /// Label edges with their weights in DOT output
+    #[arg(long)]
+    dot_weights: bool,
+    /// Decimal places of the coordinates in polyline output
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(5..=6))]
+    polyline_precision: u32,
+    /// Binary cache of the prepared graph, reused while the input file is unchanged
+    #[arg(long)]
+    cache: Option<String>,
+    #[command(flatten)]
+    csv: CsvArgs,
+}
+
+/// Column names and delimiter of CSV input, which CSV output uses too
+#[cfg(feature = "csv")]
+#[derive(Args)]
+pub struct CsvArgs {
+    /// CSV nodes file giving the ids, and optionally the positions, of the nodes named by a
+    /// CSV edges input
+    #[arg(long)]
+    csv_nodes: Option<String>,
+    #[arg(long, default_value = "from")]
+    csv_from: String,
+    #[arg(long, default_value = "to")]
+    csv_to: String,
+    /// Column holding each edge's weight
+    #[arg(long, default_value = "length")]
+    csv_length: String,
+    /// Column of the nodes file holding each node's id
+    #[arg(long, default_value = "id")]
+    csv_node_id: String,
+    #[arg(long, default_value_t = ',', value_parser = parse_csv_delimiter)]
+    csv_delimiter: char,
+}
+
+/// Column names and delimiter of CSV input, which this build cannot read
+#[cfg(not(feature = "csv"))]
+#[derive(Args)]
+pub struct CsvArgs {}
+
+#[cfg(feature = "csv")]
+fn parse_csv_delimiter(delimiter: &str) -> Result<char, String> {
+    match delimiter.chars().collect::<Vec<_>>()[..] {
+        [c] if c.is_ascii() => Ok(c),
+        _ => Err("expected a single ASCII character".to_string()),
+    }
 }
 
-pub struct RoutingResults<N> {
+#[cfg(feature = "csv")]
+impl CsvArgs {
+    fn options(&self) -> CsvOptions {
+        CsvOptions {
+            delimiter: self.csv_delimiter as u8,
+            node_id: self.csv_node_id.clone(),
+            edge_from: self.csv_from.clone(),
+            edge_to: self.csv_to.clone(),
+            edge_length: self.csv_length.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn input_options(&self) -> InputOptions {
+        InputOptions {
+            csv: self.options(),
+            csv_nodes_path: self.csv_nodes.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(not(feature = "csv"))]
+impl CsvArgs {
+    fn input_options(&self) -> InputOptions {
+        InputOptions::default()
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Check the circuits of a solution file against the input graph
+    Verify(VerifyArgs),
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Graph file to read, or `-` for standard input
+    #[arg(short, long)]
+    input_path: String,
+    /// Solution file to check, as written by the JSON output format
+    #[arg(short, long)]
+    solution_path: String,
+    /// Binary cache of the prepared graph, reused while the input file is unchanged
+    #[arg(long)]
+    cache: Option<String>,
+    #[command(flatten)]
+    csv: CsvArgs,
+}
+
+/// The circuits found, with edges identified by their position in the input edge list
+/// unless the input gave them ids of their own
+pub struct RoutingResults<N, E, K = usize> {
     pub upper: Vec<N>,
     pub lower: Vec<N>,
+    /// The input edges walked by each circuit, in circuit order
+    pub upper_edges: Vec<WalkedEdge<K>>,
+    pub lower_edges: Vec<WalkedEdge<K>>,
+    /// The input attributes of each edge walked, in circuit order; empty for edges the
+    /// input gave none
+    pub upper_attributes: Vec<Attributes>,
+    pub lower_attributes: Vec<Attributes>,
+    pub upper_length: E,
+    pub lower_length: E,
+    pub target_length: E,
 }
 
 // impl<N> Future for RoutingResults<N> {
@@ -56,22 +254,188 @@ pub struct RoutingResults<N> {
 //         })
 //     }
 // }
+//Readers (and the graph cache) work from files, so a graph piped to standard input is
+//copied to a temporary file first; it is created with a random name that no other file
+//has, and removed again when dropped
+fn stdin_to_temp_file() -> Result<NamedTempFile, Error> {
+    let mut file = NamedTempFile::new()?;
+    std_io::copy(&mut std_io::stdin().lock(), &mut file)?;
+    file.flush()?;
+    Ok(file)
+}
+
+//The input path less its extension (and any `.gz` or `.zst` one), so that outputs are
+//named after the input and written next to it
+fn input_stem(input_path: &str) -> PathBuf {
+    let path = Path::new(input_path);
+    let contents_path = match path.extension() {
+        Some(ext) if ext == "gz" || ext == "zst" => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    contents_path.with_extension("")
+}
+
+//Output formats writing one file per circuit name each file after its circuit, e.g.
+//`out.fit` becomes `out_upper.fit`
+fn circuit_output_path(output_path: &str, name: &str) -> String {
+    let path = Path::new(output_path);
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("_{}", name));
+    if let Some(ext) = path.extension() {
+        file_name.push(".");
+        file_name.push(ext);
+    }
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn write_output(output_path: &str, contents: &[u8]) -> Result<(), Error> {
+    if output_path == STDIO_PATH {
+        let mut stdout = std_io::stdout().lock();
+        stdout.write_all(contents)?;
+        stdout.flush()?;
+    } else {
+        fs::write(output_path, contents).with_context(|| format!("Writing {}", output_path))?;
+    }
+    Ok(())
+}
+
+//Builds the graph from the input file (or standard input), or loads it from the graph
+//cache when one is given and still matches the input; a stale or missing cache is
+//(re)written
+fn prepare_graph<N, E, Ix>(
+    input_path: &str,
+    input_options: &InputOptions,
+    cache: Option<&str>,
+) -> Result<PreparedGraph<N, E, Ix>, Error>
+where
+    Ix: IndexType + FromStr + From<u32> + Serialize + for<'de> Deserialize<'de>,
+    <Ix as FromStr>::Err: Debug,
+    N: 'static
+        + FromStr
+        + TryFrom<u32>
+        + TryFrom<i64>
+        + Debug
+        + Eq
+        + Hash
+        + Copy
+        + PartialOrd
+        + Serialize
+        + for<'de> Deserialize<'de>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: 'static + FromStr + From<f64> + Copy + PartialOrd + Serialize + for<'de> Deserialize<'de>,
+{
+    let stdin_file = (input_path == STDIO_PATH)
+        .then(stdin_to_temp_file)
+        .transpose()
+        .context("Reading standard input")?;
+    let stdin_path = stdin_file
+        .as_ref()
+        .map(|file| {
+            file.path()
+                .to_str()
+                .ok_or_else(|| anyhow!("{} is not a valid UTF-8 path", file.path().display()))
+        })
+        .transpose()?;
+    let (input_path, input_name) = match stdin_path {
+        Some(stdin_path) => (stdin_path, "standard input"),
+        None => (input_path, input_path),
+    };
+
+    if let Some(cache_path) = cache {
+        if let Some(prepared) = load_graph_cache(cache_path, input_path, input_options)? {
+            eprintln!("Loaded graph from cache {}", cache_path);
+            return Ok(prepared);
+        }
+    }
+
+    let (gr, format) = read_graph::<N, E, Ix>(input_path, input_options)?;
+    eprintln!("Read {} as {}", input_name, format);
+    let coordinates = gr.node_coordinates();
+    let attributes = gr.attributes();
+    let edge_geometries = gr.edge_geometries.clone();
+    let (graph, node_index_mapper) = make_graph::<&'static StableGraph<N, E, Directed, Ix>, Ix>(gr);
+    let prepared = PreparedGraph {
+        graph,
+        node_index_mapper,
+        coordinates,
+        attributes,
+        edge_geometries,
+    };
+
+    if let Some(cache_path) = cache {
+        save_graph_cache(cache_path, input_path, input_options, &prepared)?;
+    }
+    Ok(prepared)
+}
+
+//The closed circuit check of the search loop cannot notice an edge walked twice, so circuits
+//are checked in full before any is written or returned
+#[allow(clippy::type_complexity)]
+fn check_circuits<N, E, Ix>(
+    graph: &StableDiGraph<N, E, Ix>,
+    circuits: &[(&str, &EulerCircuit<StableDiGraph<N, E, Ix>>)],
+) -> Result<(), Error>
+where
+    N: Eq + Hash + Copy + Debug,
+    E: Copy + PartialOrd + Sum + Into<f64> + Debug,
+    Ix: IndexType,
+{
+    let node_indices = node_label_index(graph);
+    for (name, circuit) in circuits {
+        let mut solution =
+            CircuitSolution::from_nodes(name, circuit.ordered_node_weight_list.clone());
+        solution.edges = circuit.edge_list.clone();
+        let report = verify_circuit(graph, &node_indices, &solution);
+        if !report.is_valid() {
+            return Err(anyhow!(
+                "The {} circuit found is invalid: {}",
+                name,
+                report
+                    .problems
+                    .iter()
+                    .map(|problem| problem.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn check_loop_counter(counter: i32) -> Option<i32> {
     match counter + 1 {
-        1..=50 => Some(counter + 1),
+        1..=MAX_DOUBLE_PATH_ITERATIONS => Some(counter + 1),
         _ => None,
     }
 }
+/// Finds circuits on the graph named by `args` and writes them out, also returning them when
+/// `return_routes` is set
 pub fn make_route_from_dimacs<N, E, Ix>(
     args: CLIArgs,
     return_routes: bool,
-) -> Result<RoutingResults<N>, Error>
+) -> Result<Option<RoutingResults<N, E>>, Error>
 where
-    Ix: IndexType + FromStr + From<u32>,
+    Ix: IndexType + FromStr + From<u32> + Serialize + for<'de> Deserialize<'de>,
     <Ix as FromStr>::Err: Debug,
-    N: 'static + FromStr + Debug + Eq + Hash + Copy + Serialize,
+    N: 'static
+        + FromStr
+        + TryFrom<u32>
+        + TryFrom<i64>
+        + Debug
+        + Display
+        + Eq
+        + Hash
+        + Copy
+        + PartialOrd
+        + Serialize
+        + for<'de> Deserialize<'de>,
+    <N as TryFrom<u32>>::Error: Debug,
     E: 'static
-        + From<Ix>
+        + FromStr
+        + Serialize
+        + for<'de> Deserialize<'de>
         + Copy
         + Debug
         + Measure
@@ -82,118 +446,292 @@ where
         + Div<f64, Output = E>
         + Add<f64, Output = E>
         + From<f64>
+        + Into<f64>
         + Neg<Output = E>
         + Mul<Output = E>
+        + Sub<Output = E>
         + Sum,
 {
-    if let Ok(gr) = read_from_dimacs::<N, E, Ix>(&args.input_path) {
-        let max_dist = args.target_length * (0.6);
-        let target_length = args.target_length;
-
-        println!("{:?}", &gr.node_map.get(&args.source_vertex.into()));
+    let PreparedGraph {
+        mut graph,
+        coordinates,
+        attributes,
+        edge_geometries,
+        ..
+    } = prepare_graph::<N, E, Ix>(
+        &args.input_path,
+        &args.csv.input_options(),
+        args.cache.as_deref(),
+    )?;
+    let max_dist = args.target_length * TRIM_FACTOR;
+    let target_length = args.target_length;
 
-        let (mut graph, node_index_mapper) =
-            make_graph::<&'static StableGraph<N, E, Directed, Ix>, Ix>(gr);
+    eprintln!(
+        "Nodes: {}, Edges: {}",
+        &graph.node_count(),
+        &graph.edge_count()
+    );
 
-        println!(
-            "Nodes: {}, Edges: {}",
-            &graph.node_count(),
-            &graph.edge_count()
-        );
+    let starting_node = args
+        .source_vertex
+        .parse::<N>()
+        .ok()
+        .and_then(|label| node_label_index(&graph).get(&label).copied())
+        .ok_or_else(|| anyhow!("Invalid source vertex {}", args.source_vertex))?;
 
-        let starting_node = *node_index_mapper
-            .get(&args.source_vertex.into())
-            .expect("Invalid source vertex");
+    // let (distances, predecessor_map, predecessor_tree) =
+    //     get_distances(&graph, starting_node, max_dist);
 
-        println!("{:?}", &graph.node_weight(starting_node));
-        // let (distances, predecessor_map, predecessor_tree) =
-        //     get_distances(&graph, starting_node, max_dist);
+    let distances = dijkstra(&graph, starting_node, None, |e| *e.weight());
+    //Trimming removes nodes from `graph` itself, so GraphML export of the whole input needs a copy
+    let full_graph = (args.output_format == OutputFormat::Graphml && !args.graphml_trimmed)
+        .then(|| graph.clone());
+    let trimmed_graph = trim_graph_at_max_distance(&mut graph, &distances, max_dist.into());
 
-        let distances = dijkstra(&graph, starting_node, None, |e| *e.weight());
-        let trimmed_graph = trim_graph_at_max_distance(&mut graph, &distances, max_dist.into());
+    eprintln!(
+        "Nodes: {}, Edges: {}",
+        &trimmed_graph.graph.node_count(),
+        &trimmed_graph.graph.edge_count()
+    );
+    let mut upper_ec;
+    let mut lower_ec;
+    let mut double_path_iterations = 1;
+    loop {
+        if let Some((lower_bound, upper_bound)) = double_path::<StableDiGraph<N, E, Ix>, Ix>(
+            starting_node,
+            &trimmed_graph,
+            target_length.into(),
+        ) {
+            //The search is deterministic, so running it again would find no more circuits
+            if upper_bound.edges.is_empty() || lower_bound.edges.is_empty() {
+                return Err(anyhow!(NO_CIRCUITS_FOUND));
+            }
 
-        println!(
-            "Nodes: {}, Edges: {}",
-            &trimmed_graph.graph.node_count(),
-            &trimmed_graph.graph.edge_count()
-        );
-        let mut upper_ec;
-        let mut lower_ec;
-        let mut double_path_iterations = 1;
-        loop {
-            if let Some((lower_bound, upper_bound)) = double_path::<StableDiGraph<N, E, Ix>, Ix>(
+            upper_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
+                &graph,
+                &upper_bound,
                 starting_node,
-                &trimmed_graph,
-                target_length.into(),
-            ) {
-                println!("{:?}", &upper_bound);
-
-                upper_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
-                    &graph,
-                    &upper_bound,
-                    starting_node,
-                );
-                println!("{:?}", &upper_ec);
-                lower_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
-                    &graph,
-                    &lower_bound,
-                    starting_node,
-                );
+            )?;
+            lower_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
+                &graph,
+                &lower_bound,
+                starting_node,
+            )?;
 
-                if upper_ec.ordered_node_weight_list.first()
-                    == upper_ec.ordered_node_weight_list.last()
-                    && lower_ec.ordered_node_weight_list.first()
-                        == lower_ec.ordered_node_weight_list.last()
-                {
-                    break;
-                } else {
-                    double_path_iterations += 1;
-                    println!("Double path iterations: {:}", double_path_iterations);
-                    if double_path_iterations > 50 {
-                        return Err(anyhow!(
-                            "Unable to locate valid circuit within 50 iterations."
-                        ));
-                    }
+            if upper_ec.ordered_node_weight_list.first() == upper_ec.ordered_node_weight_list.last()
+                && lower_ec.ordered_node_weight_list.first()
+                    == lower_ec.ordered_node_weight_list.last()
+            {
+                break;
+            } else {
+                double_path_iterations += 1;
+                eprintln!("Double path iterations: {:}", double_path_iterations);
+                if double_path_iterations > MAX_DOUBLE_PATH_ITERATIONS {
+                    return Err(anyhow!(
+                        "Unable to locate valid circuit within {} iterations.",
+                        MAX_DOUBLE_PATH_ITERATIONS
+                    ));
                 }
             }
+        } else {
+            return Err(anyhow!(SEARCH_FAILED));
         }
+    }
 
-        let solutions_vector = vec![
-            upper_ec.ordered_node_weight_list,
-            lower_ec.ordered_node_weight_list,
-        ];
+    check_circuits(&graph, &[("upper", &upper_ec), ("lower", &lower_ec)])?;
 
-        let _ = write_solution_strings_to_file(
-            &[
-                &args
-                    .input_path
-                    .split('.')
-                    .next()
-                    .expect("Filepath should contain one or more parts after splitting"),
-                "sols.txt",
-            ]
-            .join("_"),
-            serde_json::to_string(&solutions_vector).unwrap(),
-        );
+    let output_path = match &args.output {
+        Some(output_path) => output_path.clone(),
+        None if args.input_path == STDIO_PATH => STDIO_PATH.to_string(),
+        None => format!(
+            "{}{}",
+            input_stem(&args.input_path).to_string_lossy(),
+            args.output_format.default_suffix()
+        ),
+    };
+    let circuits = [("upper", &upper_ec), ("lower", &lower_ec)];
+    let results = RoutingResults {
+        upper: upper_ec.ordered_node_weight_list.clone(),
+        lower: lower_ec.ordered_node_weight_list.clone(),
+        upper_edges: upper_ec.edge_list.clone(),
+        lower_edges: lower_ec.edge_list.clone(),
+        upper_attributes: attributes.walked_edges(&upper_ec.edge_list),
+        lower_attributes: attributes.walked_edges(&lower_ec.edge_list),
+        upper_length: upper_ec.length,
+        lower_length: lower_ec.length,
+        target_length: target_length.into(),
+    };
 
-        if return_routes {
-            return Ok(RoutingResults {
-                upper: solutions_vector[0].clone(),
-                lower: solutions_vector[1].clone(),
+    let contents = match args.output_format {
+        OutputFormat::Json => {
+            let mut document = SolutionDocument::new(vec![
+                CircuitSolution::from_circuit("upper", &upper_ec, target_length.into()),
+                CircuitSolution::from_circuit("lower", &lower_ec, target_length.into()),
+            ]);
+            document.input_path = (args.input_path != STDIO_PATH).then(|| args.input_path.clone());
+            document.source = graph.node_weight(starting_node).copied();
+            document.target_length = Some(target_length.into());
+            document.trim_radius = Some(max_dist.into());
+            document.solver = Some(SolverParameters {
+                trim_factor: TRIM_FACTOR,
+                max_iterations: MAX_DOUBLE_PATH_ITERATIONS as u32,
+                iterations: double_path_iterations as u32,
             });
+            solutions_to_json(&document)?.into_bytes()
+        }
+        OutputFormat::Geojson => GeoJson::from(circuits_to_geojson(
+            &results,
+            &coordinates,
+            &edge_geometries,
+        )?)
+        .to_string()
+        .into_bytes(),
+        OutputFormat::Gpx => {
+            let options = GpxOptions {
+                kind: if args.gpx_track {
+                    GpxKind::Track
+                } else {
+                    GpxKind::Route
+                },
+                waypoints: args.gpx_waypoints,
+                ..Default::default()
+            };
+            circuits_to_gpx(
+                &circuits,
+                target_length.into(),
+                &coordinates,
+                &edge_geometries,
+                &options,
+            )?
+            .into_bytes()
+        }
+        //Each circuit is a course file of its own, so there is no single output to write
+        OutputFormat::Fit => {
+            if output_path == STDIO_PATH {
+                return Err(anyhow!(
+                    "FIT output is one file per circuit, and cannot be written to standard output"
+                ));
+            }
+            for (name, circuit) in circuits {
+                let options = FitOptions {
+                    name: format!("{} circuit", name),
+                    ..Default::default()
+                };
+                write_output(
+                    &circuit_output_path(&output_path, name),
+                    &circuit_to_fit(circuit, &coordinates, &edge_geometries, &options)?,
+                )?;
+            }
+            return Ok(return_routes.then_some(results));
+        }
+        OutputFormat::Graphml => graph_to_graphml(
+            full_graph.as_ref().unwrap_or(&trimmed_graph.graph),
+            &circuits,
+            &coordinates,
+            &attributes,
+            &GraphMlOptions::default(),
+        )
+        .into_bytes(),
+        OutputFormat::Dot => graph_to_dot(
+            &trimmed_graph.graph,
+            &[
+                ("upper", &upper_ec.edge_list),
+                ("lower", &lower_ec.edge_list),
+            ],
+            starting_node,
+            &DotOptions {
+                edge_weights: args.dot_weights,
+            },
+        )
+        .into_bytes(),
+        OutputFormat::Polyline => circuits_to_polyline(
+            &circuits,
+            &coordinates,
+            &edge_geometries,
+            &PolylineOptions {
+                precision: args.polyline_precision,
+            },
+        )?
+        .into_bytes(),
+        OutputFormat::Wkt => {
+            circuits_to_wkt(&circuits, &coordinates, &edge_geometries)?.into_bytes()
+        }
+        #[cfg(feature = "csv")]
+        OutputFormat::Csv => {
+            circuits_to_csv(&circuits, &coordinates, &args.csv.options())?.into_bytes()
+        }
+    };
+    write_output(&output_path, &contents)?;
+
+    Ok(return_routes.then_some(results))
+}
+
+/// Checks each circuit of a solution file against the graph it was found on, printing the
+/// problems found; fails unless every circuit is valid
+pub fn verify_solution_from_dimacs<N, E, Ix>(args: VerifyArgs) -> Result<(), Error>
+where
+    Ix: IndexType + FromStr + From<u32> + Serialize + for<'de> Deserialize<'de>,
+    <Ix as FromStr>::Err: Debug,
+    N: 'static
+        + FromStr
+        + TryFrom<u32>
+        + TryFrom<i64>
+        + Debug
+        + Eq
+        + Hash
+        + Copy
+        + PartialOrd
+        + Serialize
+        + for<'de> Deserialize<'de>,
+    <N as TryFrom<u32>>::Error: Debug,
+    E: 'static
+        + FromStr
+        + From<f64>
+        + Into<f64>
+        + Copy
+        + Debug
+        + PartialOrd
+        + Sum
+        + Serialize
+        + for<'de> Deserialize<'de>,
+{
+    let document = read_solutions::<N, E, usize>(&args.solution_path)?;
+    let PreparedGraph { graph, .. } = prepare_graph::<N, E, Ix>(
+        &args.input_path,
+        &args.csv.input_options(),
+        args.cache.as_deref(),
+    )?;
+
+    let reports = verify_solutions(&graph, &document);
+    for report in &reports {
+        if report.is_valid() {
+            println!("{}: valid, length {:?}", report.name, report.length);
+        } else {
+            println!("{}: invalid, length {:?}", report.name, report.length);
+            report
+                .problems
+                .iter()
+                .for_each(|problem| println!("  {}", problem));
         }
     }
 
-    Err(anyhow!(
-        "Failed to produce valid circuit for provided input."
-    ))
+    let invalid = reports.iter().filter(|report| !report.is_valid()).count();
+    if invalid > 0 {
+        return Err(anyhow!(
+            "{} of {} circuits in {} failed verification",
+            invalid,
+            reports.len(),
+            args.solution_path
+        ));
+    }
+    Ok(())
 }
 
 pub fn make_route_from_edges_json<N, E, Ix>(
     json_string: String,
     source_vertex_id: N,
     target_length: E,
-) -> Result<RoutingResults<N>, Error>
+) -> Result<RoutingResults<N, E, N>, Error>
 where
     Ix: IndexType + FromStr + From<u32>,
     <Ix as FromStr>::Err: Debug,
@@ -201,7 +739,7 @@ where
     for<'de> E: Deserialize<'de>,
     N: 'static + FromStr + Debug + Eq + Hash + Copy + PartialOrd,
     E: 'static
-        + From<Ix>
+        + FromStr
         + Copy
         + Debug
         + Measure
@@ -212,32 +750,31 @@ where
         + Div<f64, Output = E>
         + Add<f64, Output = E>
         + From<f64>
+        + Into<f64>
         + Neg<Output = E>
         + Mul<Output = E>
         + Sum,
 {
-    println!("Source vertex ID: {:?}", &source_vertex_id);
-    println!("Target distance: {:?}", &target_length);
-    if let Ok((gr, weight_to_node_id)) = read_from_edges_json::<N, E, Ix>(json_string) {
-        println!("Made graph from provided JSON");
-        let max_dist = target_length * (0.6.into());
+    eprintln!("Source vertex ID: {:?}", &source_vertex_id);
+    eprintln!("Target distance: {:?}", &target_length);
+    if let Ok((gr, weight_to_node_id, edge_ids)) = read_from_edges_json::<N, E, Ix>(json_string) {
+        eprintln!("Made graph from provided JSON");
+        let max_dist = target_length * TRIM_FACTOR.into();
+        let attributes = gr.attributes();
 
         let (mut graph, node_index_mapper) =
             make_graph::<&'static StableGraph<N, E, Directed, Ix>, Ix>(gr);
 
         let starting_node = match weight_to_node_id.get(&source_vertex_id) {
-            Some(idx) => {
-                println!("{:?}", &idx);
-                match node_index_mapper.get(idx) {
-                    Some(node_idx) => *node_idx,
-                    None => return Err(anyhow!("Node index not found")),
-                }
-            }
+            Some(idx) => match node_index_mapper.get(idx) {
+                Some(node_idx) => *node_idx,
+                None => return Err(anyhow!("Node index not found")),
+            },
             None => return Err(anyhow!("Invalid source vertex")),
         };
 
         let distances = dijkstra(&graph, starting_node, None, |e| *e.weight());
-        let trimmed_graph = trim_graph_at_max_distance(&mut graph, &distances, max_dist.into());
+        let trimmed_graph = trim_graph_at_max_distance(&mut graph, &distances, max_dist);
 
         let mut upper_ec;
         let mut lower_ec;
@@ -246,34 +783,22 @@ where
             if let Some((lower_bound, upper_bound)) = double_path::<StableDiGraph<N, E, Ix>, Ix>(
                 starting_node,
                 &trimmed_graph,
-                target_length.into(),
+                target_length,
             ) {
-                if &upper_bound.edges.is_empty()
-                    | &upper_bound.vertices.is_empty()
-                    | &lower_bound.edges.is_empty()
-                    | &lower_bound.vertices.is_empty()
-                {
-                    match check_loop_counter(double_path_iterations) {
-                        Some(new) => double_path_iterations = new,
-                        None => {
-                            return Err(anyhow!(
-                                "Unable to locate valid circuit within 50 iterations."
-                            ))
-                        }
-                    }
+                //The search is deterministic, so running it again would find no more circuits
+                if upper_bound.edges.is_empty() || lower_bound.edges.is_empty() {
+                    return Err(anyhow!(NO_CIRCUITS_FOUND));
                 }
-                println!("{:?}", &upper_bound);
                 upper_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
                     &graph,
                     &upper_bound,
                     starting_node,
-                );
-                println!("{:?}", &upper_ec);
+                )?;
                 lower_ec = make_euler_circuit::<StableDiGraph<N, E, Ix>, Ix>(
                     &graph,
                     &lower_bound,
                     starting_node,
-                );
+                )?;
 
                 if upper_ec.ordered_node_weight_list.first()
                     == upper_ec.ordered_node_weight_list.last()
@@ -286,23 +811,41 @@ where
                         Some(new) => double_path_iterations = new,
                         None => {
                             return Err(anyhow!(
-                                "Unable to locate valid circuit within 50 iterations."
+                                "Unable to locate valid circuit within {} iterations.",
+                                MAX_DOUBLE_PATH_ITERATIONS
                             ))
                         }
                     }
                 }
+            } else {
+                return Err(anyhow!(SEARCH_FAILED));
             }
         }
 
-        let solutions_vector = vec![
+        check_circuits(&graph, &[("upper", &upper_ec), ("lower", &lower_ec)])?;
+
+        let solutions_vector = [
             upper_ec.ordered_node_weight_list,
             lower_ec.ordered_node_weight_list,
         ];
+        let caller_edges = |edge_list: &[WalkedEdge<usize>]| -> Vec<WalkedEdge<N>> {
+            edge_list
+                .iter()
+                .map(|edge| edge.map_id(|id| edge_ids[id]))
+                .collect()
+        };
 
-        return Ok(RoutingResults {
+        Ok(RoutingResults {
             upper: solutions_vector[0].clone(),
             lower: solutions_vector[1].clone(),
-        });
+            upper_edges: caller_edges(&upper_ec.edge_list),
+            lower_edges: caller_edges(&lower_ec.edge_list),
+            upper_attributes: attributes.walked_edges(&upper_ec.edge_list),
+            lower_attributes: attributes.walked_edges(&lower_ec.edge_list),
+            upper_length: upper_ec.length,
+            lower_length: lower_ec.length,
+            target_length,
+        })
     } else {
         Err(anyhow!(
             "Failed to produce valid circuit for provided input."
@@ -431,3 +974,256 @@ where
 //         ))
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/DIMACS_sample.txt");
+
+    #[test]
+    fn sample_circuits_pass_verification() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path().join("sols.json");
+        let output = output.to_str().unwrap();
+        let args = CLIArgs::parse_from([
+            "speedicycle",
+            "-i",
+            SAMPLE,
+            "-s",
+            "32738",
+            "-t",
+            "5000",
+            "-o",
+            output,
+        ]);
+
+        let results = make_route_from_dimacs::<u64, f64, u32>(args, true)
+            .unwrap()
+            .unwrap();
+        let document = read_solutions::<u64, f64, usize>(output).unwrap();
+        let PreparedGraph { graph, .. } =
+            prepare_graph::<u64, f64, u32>(SAMPLE, &InputOptions::default(), None).unwrap();
+
+        for report in verify_solutions(&graph, &document) {
+            assert!(report.is_valid(), "{}: {:?}", report.name, report.problems);
+        }
+        assert_eq!(
+            document.circuit("upper").unwrap().edges,
+            results.upper_edges
+        );
+        assert_eq!(
+            document.circuit("lower").unwrap().edges,
+            results.lower_edges
+        );
+    }
+
+    #[test]
+    fn fails_when_there_are_no_circuits() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("path.txt");
+        fs::write(&input, "p 3 2\nv 10\nv 11\nv 12\ne 0 1 1\ne 1 2 1\n").unwrap();
+        let args = CLIArgs::parse_from([
+            "speedicycle",
+            "-i",
+            input.to_str().unwrap(),
+            "-s",
+            "10",
+            "-t",
+            "4",
+        ]);
+
+        let error = make_route_from_dimacs::<u64, f64, u32>(args, false)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), NO_CIRCUITS_FOUND);
+        assert!(!dir.path().join("path_sols.txt").exists());
+    }
+
+    #[test]
+    fn takes_the_source_by_its_label_rather_than_its_position() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("path.txt");
+        fs::write(&input, "p 3 2\nv 10\nv 11\nv 12\ne 0 1 1\ne 1 2 1\n").unwrap();
+        for source in ["0", "ten"] {
+            let args = CLIArgs::parse_from([
+                "speedicycle",
+                "-i",
+                input.to_str().unwrap(),
+                "-s",
+                source,
+                "-t",
+                "4",
+            ]);
+
+            let error = make_route_from_dimacs::<u64, f64, u32>(args, false)
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("Invalid source vertex {}", source)
+            );
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn parses_routing_arguments_alongside_csv_options() {
+        let cli = Cli::parse_from([
+            "speedicycle",
+            "-i",
+            "edges.csv",
+            "-s",
+            "0",
+            "-t",
+            "5000",
+            "--csv-delimiter",
+            ";",
+        ]);
+        let args = cli.route.unwrap();
+        assert_eq!(args.csv.options().delimiter, b';');
+        assert!(cli.command.is_none());
+
+        let cli = Cli::parse_from(["speedicycle", "verify", "-i", "g.txt", "-s", "sols.txt"]);
+        assert!(cli.route.is_none());
+        assert!(Cli::try_parse_from(["speedicycle", "--csv-delimiter", "::"]).is_err());
+    }
+
+    //A 4 by 4 grid of nodes 0 to 15, with edge ids from 100 and weights of 1, 1.5 and 2
+    fn grid_edges_json() -> String {
+        let mut edges = Vec::new();
+        for node in 0..16u64 {
+            let neighbours = [
+                (node % 4 < 3).then_some(node + 1),
+                (node < 12).then_some(node + 4),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                let id = 100 + edges.len();
+                edges.push(format!(
+                    r#"{{"edge_id": {}, "start_node": {}, "end_node": {}, "weight": {}}}"#,
+                    id,
+                    node,
+                    neighbour,
+                    1.0 + (id % 3) as f64 * 0.5
+                ));
+            }
+        }
+        format!("[{}]", edges.join(","))
+    }
+
+    #[test]
+    fn routes_edges_json_on_either_side_of_the_target() {
+        let results =
+            make_route_from_edges_json::<u64, f64, u32>(grid_edges_json(), 5, 7.0).unwrap();
+
+        //Nodes are numbered in hash order, so which of several equally long circuits is found
+        //may vary
+        for circuit in [&results.upper, &results.lower] {
+            assert_eq!((circuit.first(), circuit.last()), (Some(&5), Some(&5)));
+        }
+        assert!(results.upper_length >= 7.0 && results.lower_length <= 7.0);
+        assert_eq!(results.upper_edges.len(), results.upper.len() - 1);
+        assert!(results
+            .upper_edges
+            .iter()
+            .chain(&results.lower_edges)
+            .all(|edge| (100..124).contains(&edge.id)));
+    }
+
+    #[test]
+    fn rejects_circuits_walking_an_edge_twice() {
+        let mut graph = StableDiGraph::<u64, f64, u32>::default();
+        let a = graph.add_node(1);
+        let b = graph.add_node(2);
+        graph.add_edge(a, b, 1.0);
+        graph.add_edge(b, a, 1.0);
+        let there_and_back = EulerCircuit::<StableDiGraph<u64, f64, u32>> {
+            length: 2.0,
+            ordered_node_weight_list: vec![1, 2, 1],
+            edge_list: vec![
+                WalkedEdge {
+                    id: 0,
+                    forward: true,
+                },
+                WalkedEdge {
+                    id: 0,
+                    forward: false,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let error = check_circuits(&graph, &[("upper", &there_and_back)]).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("The upper circuit found is invalid: "));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn carries_input_attributes_through_trimming_to_the_edges_walked() {
+        //The 4 by 4 grid again, with a spur to a node beyond the trimming radius
+        let dir = TempDir::new().unwrap();
+        let mut nodes = String::from("id,kind\n");
+        let mut edges = String::from("from,to,length,name\n");
+        for node in 0..16u64 {
+            nodes.push_str(&format!("{},junction\n", node));
+            let neighbours = [
+                (node % 4 < 3).then_some(node + 1),
+                (node < 12).then_some(node + 4),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                let id = edges.lines().count() - 1;
+                let w = 1.0 + ((100 + id) % 3) as f64 * 0.5;
+                edges.push_str(&format!("{},{},{},street {}\n", node, neighbour, w, id));
+            }
+        }
+        nodes.push_str("16,dead end\n");
+        edges.push_str("15,16,100,spur\n");
+        let nodes_path = dir.path().join("nodes.csv");
+        let edges_path = dir.path().join("edges.csv");
+        fs::write(&nodes_path, nodes).unwrap();
+        fs::write(&edges_path, edges).unwrap();
+        let args = CLIArgs::parse_from([
+            "speedicycle",
+            "-i",
+            edges_path.to_str().unwrap(),
+            "--csv-nodes",
+            nodes_path.to_str().unwrap(),
+            "-s",
+            "5",
+            "-t",
+            "7",
+            "-o",
+            dir.path().join("sols.txt").to_str().unwrap(),
+        ]);
+
+        let results = make_route_from_dimacs::<u64, f64, u32>(args, true)
+            .unwrap()
+            .unwrap();
+        for (edges, attributes) in [
+            (&results.upper_edges, &results.upper_attributes),
+            (&results.lower_edges, &results.lower_attributes),
+        ] {
+            assert!(!edges.is_empty());
+            assert_eq!(attributes.len(), edges.len());
+            for (edge, attrs) in edges.iter().zip(attributes) {
+                assert_eq!(attrs["name"], format!("street {}", edge.id));
+                assert_eq!(attrs.len(), 1);
+            }
+        }
+
+        let PreparedGraph { attributes, .. } = prepare_graph::<u64, f64, u32>(
+            edges_path.to_str().unwrap(),
+            &InputOptions {
+                csv_nodes_path: Some(nodes_path.to_str().unwrap().to_string()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(attributes.node(&16).unwrap()["kind"], "dead end");
+        assert_eq!(attributes.edge(24).unwrap()["name"], "spur");
+    }
+}
@@ -2,10 +2,14 @@ extern crate speedicycle;
 use anyhow::Error;
 use clap::Parser;
 
-use speedicycle::{make_route_from_dimacs, CLIArgs};
+use speedicycle::{make_route_from_dimacs, verify_solution_from_dimacs, Cli, Command};
 
 fn main() -> Result<(), Error> {
-    let args: CLIArgs = CLIArgs::parse();
+    let cli = Cli::parse();
 
-    make_route_from_dimacs::<u32, f64, u32>(args, false).map(|_i| ())
+    match (cli.command, cli.route) {
+        (Some(Command::Verify(args)), _) => verify_solution_from_dimacs::<u64, f64, u32>(args),
+        (None, Some(args)) => make_route_from_dimacs::<u64, f64, u32>(args, false).map(|_i| ()),
+        (None, None) => unreachable!("clap requires the routing arguments without a subcommand"),
+    }
 }
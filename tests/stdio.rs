@@ -0,0 +1,77 @@
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+//A 4 by 4 grid in DIMACS, its nodes labelled 100 to 115 and its edges 1, 1.5 or 2 long
+fn grid_dimacs() -> String {
+    let mut edges = Vec::new();
+    for node in 0..16 {
+        let neighbours = [
+            (node % 4 < 3).then_some(node + 1),
+            (node < 12).then_some(node + 4),
+        ];
+        for neighbour in neighbours.into_iter().flatten() {
+            let w = 1.0 + ((100 + edges.len()) % 3) as f64 * 0.5;
+            edges.push(format!("e {} {} {}", node, neighbour, w));
+        }
+    }
+    let nodes: Vec<String> = (100..116).map(|label| format!("v {}", label)).collect();
+    format!(
+        "p 16 {}\n{}\n{}\n",
+        edges.len(),
+        nodes.join("\n"),
+        edges.join("\n")
+    )
+}
+
+fn run(args: &[&str], stdin: &str) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_speedicycle"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn routes_a_graph_from_standard_input_to_standard_output() {
+    let output = run(
+        &["-i", "-", "-o", "-", "-s", "105", "-t", "7"],
+        &grid_dimacs(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["source"], 105);
+    assert!(document["input_path"].is_null());
+    let circuits = document["circuits"].as_array().unwrap();
+    assert_eq!(circuits.len(), 2);
+    for circuit in circuits {
+        let nodes = circuit["nodes"].as_array().unwrap();
+        assert_eq!(nodes.first(), Some(&Value::from(105)));
+        assert_eq!(nodes.last(), Some(&Value::from(105)));
+        assert_eq!(circuit["edges"].as_array().unwrap().len(), nodes.len() - 1);
+    }
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Read standard input as DIMACS"));
+}
+
+#[test]
+fn reports_unreadable_standard_input() {
+    let output = run(&["-i", "-", "-s", "105", "-t", "7"], "not a graph\n");
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not in any supported graph format"));
+}